@@ -5,6 +5,7 @@ use test::Bencher;
 
 extern crate yoin;
 use yoin::ipadic;
+use yoin::tokenizer::{Options, Workspace};
 
 #[bench]
 fn bench_sumomo(b: &mut Bencher) {
@@ -13,6 +14,15 @@ fn bench_sumomo(b: &mut Bencher) {
     b.iter(|| tokenizer.tokenize(input))
 }
 
+#[bench]
+fn bench_sumomo_with_workspace(b: &mut Bencher) {
+    let input = "すもももももももものうち";
+    let tokenizer = ipadic::tokenizer();
+    let mut workspace = Workspace::new();
+    let mut tokens = Vec::new();
+    b.iter(|| tokenizer.tokenize_with(&mut workspace, input, &Options::new(), &mut tokens).unwrap())
+}
+
 // from wikipedia (https://ja.wikipedia.org/wiki/形態素解析)
 const LONG_TEXT: &'static str = r#"
 形態素解析（けいたいそかいせき、Morphological Analysis）とは、文法的な情報の注記の無い自然言語のテキストデータ（文）から、対象言語の文法や、辞書と呼ばれる単語の品詞等の情報にもとづき、形態素（Morpheme, おおまかにいえば、言語で意味を持つ最小単位）の列に分割し、それぞれの形態素の品詞等を判別する作業である。
@@ -28,6 +38,16 @@ fn bench_long_text(b: &mut Bencher) {
     })
 }
 
+#[bench]
+fn bench_long_text_with_workspace(b: &mut Bencher) {
+    let tokenizer = ipadic::tokenizer();
+    let mut workspace = Workspace::new();
+    let mut tokens = Vec::new();
+    b.iter(|| {
+        tokenizer.tokenize_with(&mut workspace, LONG_TEXT, &Options::new(), &mut tokens).unwrap();
+    })
+}
+
 #[bench]
 fn bench_5_times_long_text(b: &mut Bencher) {
     let tokenizer = ipadic::tokenizer();
@@ -8,12 +8,70 @@ use clap::{Arg, App};
 extern crate yoin;
 
 use yoin::ipadic;
+use yoin::tokenizer::{ConstraintError, Mode, Options as TokenizerOptions, Tokenizer};
+use yoin::ud::ConlluWriter;
+use yoin::romaji::{Romanizer, System};
 
-fn read_and_analyze_lines<R: io::BufRead>(r: R) -> io::Result<()> {
-    let tokenizer = ipadic::tokenizer();
+enum Format {
+    MeCab,
+    Conllu,
+    Romaji(Romanizer),
+}
+
+struct Options {
+    nbest: usize,
+    mode: Mode,
+    format: Format,
+}
+
+fn invalid_input(e: ConstraintError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, e)
+}
+
+fn read_and_analyze_lines<R: io::BufRead>(r: R,
+                                          tokenizer: &Tokenizer,
+                                          opts: &Options)
+                                          -> io::Result<()> {
+    let options = TokenizerOptions::new().with_mode(opts.mode);
+    let stdout = io::stdout();
+    let mut conllu = ConlluWriter::new(stdout.lock());
     for line in r.lines() {
         let line = line?;
-        for node in tokenizer.tokenize(line.as_str()) {
+        match opts.format {
+            Format::Conllu => {
+                let tokens = tokenizer.tokenize_with_options(line.as_str(), &options)
+                    .map_err(invalid_input)?;
+                conllu.write_sentence(&line, &tokens)?;
+                continue;
+            }
+            Format::Romaji(ref romanizer) => {
+                let words = tokenizer.tokenize_with_options(line.as_str(), &options)
+                    .map_err(invalid_input)?
+                    .iter()
+                    .filter(|t| !t.surface().trim().is_empty())
+                    .map(|t| match t.pronunciation() {
+                        Some(p) => romanizer.romanize(&p),
+                        None => t.surface().to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", words.join(" "));
+                continue;
+            }
+            Format::MeCab => {}
+        }
+        if opts.nbest > 1 {
+            let paths = tokenizer.tokenize_nbest(line.as_str(), opts.nbest, &options)
+                .map_err(invalid_input)?;
+            for path in paths {
+                for node in path.tokens() {
+                    println!("{}", node);
+                }
+                println!("EOS");
+            }
+            continue;
+        }
+        for node in tokenizer.tokenize_with_options(line.as_str(), &options)
+            .map_err(invalid_input)? {
             println!("{}", node);
         }
         println!("EOS");
@@ -30,13 +88,86 @@ fn main() {
             .value_name("FILE")
             .help("input file. if not specified, read from stdin")
             .takes_value(true))
+        .arg(Arg::with_name("udic")
+            .long("udic")
+            .value_name("FILE")
+            .help("user dictionary in the CSV format of MeCab dictionaries")
+            .takes_value(true))
+        .arg(Arg::with_name("nbest")
+            .short("N")
+            .long("nbest")
+            .value_name("N")
+            .help("output N best results. only for the mecab format")
+            .takes_value(true)
+            .validator(|n| match n.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(()),
+                _ => Err("N must be a positive integer".to_string()),
+            }))
+        .arg(Arg::with_name("mode")
+            .long("mode")
+            .value_name("MODE")
+            .help("tokenization mode")
+            .possible_values(&["normal", "search", "extended"])
+            .default_value("normal")
+            .takes_value(true))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .help("output format")
+            .possible_values(&["mecab", "conllu", "romaji"])
+            .default_value("mecab")
+            .takes_value(true))
+        .arg(Arg::with_name("romaji")
+            .long("romaji")
+            .value_name("SYSTEM")
+            .help("romanization system for the romaji format")
+            .possible_values(&["hepburn", "kunrei", "nihon"])
+            .default_value("hepburn")
+            .takes_value(true))
         .get_matches();
 
+    let nbest = match matches.value_of("nbest") {
+        Some(n) => n.parse::<usize>().unwrap(),
+        None => 1,
+    };
+    let mode = match matches.value_of("mode") {
+        Some("search") => Mode::Search,
+        Some("extended") => Mode::Extended,
+        _ => Mode::Normal,
+    };
+    let format = match matches.value_of("format") {
+        Some("conllu") => Format::Conllu,
+        Some("romaji") => {
+            let system = match matches.value_of("romaji") {
+                Some("kunrei") => System::Kunrei,
+                Some("nihon") => System::NihonShiki,
+                _ => System::Hepburn,
+            };
+            Format::Romaji(Romanizer::new(system))
+        }
+        _ => Format::MeCab,
+    };
+    if matches.is_present("nbest") && !matches!(format, Format::MeCab) {
+        clap::Error::with_description("--nbest is only supported by the mecab format",
+                                      clap::ErrorKind::ArgumentConflict)
+            .exit();
+    }
+    let opts = Options {
+        nbest: nbest,
+        mode: mode,
+        format: format,
+    };
+
+    let tokenizer = match matches.value_of("udic") {
+        Some(udic) => ipadic::tokenizer().with_udic_file(udic).unwrap(),
+        None => ipadic::tokenizer(),
+    };
+
     if let Some(file) = matches.value_of("file") {
         let file = File::open(file).unwrap();
-        read_and_analyze_lines(io::BufReader::new(file)).unwrap();
+        read_and_analyze_lines(io::BufReader::new(file), &tokenizer, &opts).unwrap();
     } else {
         let stdin = io::stdin();
-        read_and_analyze_lines(stdin.lock()).unwrap();
+        read_and_analyze_lines(stdin.lock(), &tokenizer, &opts).unwrap();
     }
 }
@@ -23,3 +23,15 @@ pub extern crate yoin_ipadic as ipadic;
 pub const VERSION: &'static str = "0.0.1";
 
 pub use core::tokenizer;
+pub use core::sentence;
+pub use core::normalize;
+pub use core::features;
+pub use core::ud;
+pub use core::reading;
+pub use core::romaji;
+pub use core::furigana;
+pub use core::mora;
+pub use core::conjugation;
+pub use core::bunsetsu;
+pub use core::compound;
+pub use core::analysis;
@@ -0,0 +1,299 @@
+//! Analysis chains like Lucene's: char filters, a tokenizer and token filters.
+//!
+//! `CharFilter`s rewrite the input before tokenization, keeping offsets of the original text.
+//! `TokenFilter`s rewrite or remove the tokens. The built-in token filters expect features in
+//! the IPADIC format.
+use std::collections::HashSet;
+
+use features::{self, Ipadic};
+use normalize::{Normalized, Normalizer};
+use tokenizer::{Mode, Options, TokenBuf, Tokenizer};
+
+pub trait CharFilter {
+    fn filter<'a>(&self, input: &'a str) -> Normalized<'a>;
+}
+
+impl CharFilter for Normalizer {
+    fn filter<'a>(&self, input: &'a str) -> Normalized<'a> {
+        self.normalize(input)
+    }
+}
+
+pub trait TokenFilter {
+    fn filter(&self, tokens: Vec<TokenBuf>) -> Vec<TokenBuf>;
+}
+
+/// Removes tokens whose parts of speech match any of the tags. A tag is a prefix of the
+/// features separated by commas, e.g. `助詞` or `助詞,格助詞`, where `*` matches any field; see
+/// `features::matches`.
+#[derive(Debug, Clone)]
+pub struct PosStopFilter {
+    tags: Vec<String>,
+}
+
+impl PosStopFilter {
+    pub fn new<I, S>(tags: I) -> Self
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>
+    {
+        PosStopFilter { tags: tags.into_iter().map(|tag| tag.as_ref().to_string()).collect() }
+    }
+
+    fn is_stopped(&self, token: &TokenBuf) -> bool {
+        let fields = || token.features.iter().map(String::as_str);
+        self.tags.iter().any(|tag| features::matches(tag, fields()))
+    }
+}
+
+impl TokenFilter for PosStopFilter {
+    fn filter(&self, mut tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        tokens.retain(|t| !self.is_stopped(t));
+        tokens
+    }
+}
+
+/// Removes tokens whose surfaces are in the stopword list.
+#[derive(Debug, Clone)]
+pub struct StopFilter {
+    words: HashSet<String>,
+}
+
+impl StopFilter {
+    pub fn new<I, S>(words: I) -> Self
+        where I: IntoIterator<Item = S>,
+              S: Into<String>
+    {
+        StopFilter { words: words.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl TokenFilter for StopFilter {
+    fn filter(&self, mut tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        tokens.retain(|t| !self.words.contains(&t.surface));
+        tokens
+    }
+}
+
+/// Replaces surfaces with their base forms, e.g. 食べ with 食べる.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BaseFormFilter;
+
+impl TokenFilter for BaseFormFilter {
+    fn filter(&self, mut tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        for token in &mut tokens {
+            if let Some(base) = token.features_as::<Ipadic>().base_form.map(|s| s.to_string()) {
+                token.surface = base;
+            }
+        }
+        tokens
+    }
+}
+
+/// Replaces surfaces with their readings in katakana. Tokens without readings, such as
+/// unknown words, are kept as they are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadingFormFilter;
+
+impl TokenFilter for ReadingFormFilter {
+    fn filter(&self, mut tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        for token in &mut tokens {
+            if let Some(reading) = token.features_as::<Ipadic>().reading.map(|s| s.to_string()) {
+                token.surface = reading;
+            }
+        }
+        tokens
+    }
+}
+
+const PROLONGED_SOUND_MARK: char = 'ー';
+const DEFAULT_MINIMUM_KATAKANA_LENGTH: usize = 4;
+
+fn is_katakana(ch: char) -> bool {
+    ('\u{30A0}'..='\u{30FF}').contains(&ch)
+}
+
+/// Removes a prolonged sound mark at the end of katakana words of at least the minimum length,
+/// e.g. コンピューター to コンピュータ, as Kuromoji's `JapaneseKatakanaStemFilter` does.
+#[derive(Debug, Clone, Copy)]
+pub struct KatakanaStemFilter {
+    minimum_length: usize,
+}
+
+impl Default for KatakanaStemFilter {
+    fn default() -> Self {
+        KatakanaStemFilter { minimum_length: DEFAULT_MINIMUM_KATAKANA_LENGTH }
+    }
+}
+
+impl KatakanaStemFilter {
+    pub fn new() -> Self {
+        KatakanaStemFilter::default()
+    }
+
+    /// Sets the minimum length in characters of words to be stemmed.
+    pub fn with_minimum_length(mut self, minimum_length: usize) -> Self {
+        self.minimum_length = minimum_length;
+        self
+    }
+}
+
+impl TokenFilter for KatakanaStemFilter {
+    fn filter(&self, mut tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        for token in &mut tokens {
+            if token.surface.ends_with(PROLONGED_SOUND_MARK) &&
+               token.surface.chars().count() >= self.minimum_length &&
+               token.surface.chars().all(is_katakana) {
+                token.surface.pop();
+            }
+        }
+        tokens
+    }
+}
+
+/// Lowercases alphabets, including full-width ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LowerCaseFilter;
+
+impl TokenFilter for LowerCaseFilter {
+    fn filter(&self, mut tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        for token in &mut tokens {
+            if token.surface.chars().any(char::is_uppercase) {
+                token.surface = token.surface.to_lowercase();
+            }
+        }
+        tokens
+    }
+}
+
+/// A chain of char filters, a tokenizer and token filters.
+pub struct Analyzer {
+    tokenizer: Tokenizer,
+    mode: Mode,
+    char_filters: Vec<Box<dyn CharFilter + Send + Sync>>,
+    token_filters: Vec<Box<dyn TokenFilter + Send + Sync>>,
+}
+
+impl Analyzer {
+    pub fn new(tokenizer: Tokenizer) -> Self {
+        Analyzer {
+            tokenizer,
+            mode: Mode::Normal,
+            char_filters: Vec::new(),
+            token_filters: Vec::new(),
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Appends a char filter, applied after the ones added before.
+    pub fn with_char_filter<F>(mut self, filter: F) -> Self
+        where F: CharFilter + Send + Sync + 'static
+    {
+        self.char_filters.push(Box::new(filter));
+        self
+    }
+
+    /// Appends a token filter, applied after the ones added before.
+    pub fn with_token_filter<F>(mut self, filter: F) -> Self
+        where F: TokenFilter + Send + Sync + 'static
+    {
+        self.token_filters.push(Box::new(filter));
+        self
+    }
+
+    /// Analyzes the input. Offsets of the tokens point into the input.
+    pub fn analyze(&self, input: &str) -> Vec<TokenBuf> {
+        let tokens = self.tokenize(&self.char_filters, input);
+        self.token_filters.iter().fold(tokens, |tokens, filter| filter.filter(tokens))
+    }
+
+    /// Applies the char filters one by one, and maps the offsets of the tokens back to `input`.
+    fn tokenize(&self,
+                char_filters: &[Box<dyn CharFilter + Send + Sync>],
+                input: &str)
+                -> Vec<TokenBuf> {
+        let (filter, rest) = match char_filters.split_first() {
+            None => {
+                return self.tokenizer
+                    .tokenize_with_options(input, &Options::new().with_mode(self.mode))
+                    .expect("options without constraints are always valid")
+                    .iter()
+                    .map(TokenBuf::from)
+                    .collect();
+            }
+            Some(pair) => pair,
+        };
+        let normalized = filter.filter(input);
+        let mut tokens = self.tokenize(rest, normalized.text());
+        for token in &mut tokens {
+            let start = normalized.original_start(token.start);
+            let end = normalized.original_end(token.end);
+            token.start = start.byte;
+            token.end = end.byte;
+            token.char_start = start.char;
+            token.char_end = end.char;
+            token.utf16_start = start.utf16;
+            token.utf16_end = end.utf16;
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+
+    fn analyze(analyzer: &Analyzer, input: &str) -> Vec<String> {
+        analyzer.analyze(input).into_iter().map(|t| t.surface).collect()
+    }
+
+    #[test]
+    fn test_stop_filters() {
+        let analyzer = Analyzer::new(Tokenizer::new(sysdic::tests::sysdic()))
+            .with_token_filter(PosStopFilter::new(["助詞,係助詞", "記号"]))
+            .with_token_filter(StopFilter::new(vec!["うち"]));
+        assert_eq!(analyze(&analyzer, "すもももももももものうち。"),
+                   vec!["すもも", "もも", "もも", "の"]);
+
+        let analyzer = Analyzer::new(Tokenizer::new(sysdic::tests::sysdic()))
+            .with_token_filter(PosStopFilter::new(["名詞,*,地域", "助詞,*,*,*,*,*,の"]));
+        assert_eq!(analyze(&analyzer, "関西のすもも"), vec!["すもも"]);
+    }
+
+    #[test]
+    fn test_form_filters() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let analyzer = Analyzer::new(tokenizer.clone())
+            .with_token_filter(BaseFormFilter)
+            .with_token_filter(ReadingFormFilter);
+        assert_eq!(analyze(&analyzer, "すもものヨイン"), vec!["スモモ", "ノ", "ヨイン"]);
+
+        let analyzer = Analyzer::new(tokenizer)
+            .with_token_filter(KatakanaStemFilter::new())
+            .with_token_filter(LowerCaseFilter);
+        assert_eq!(analyze(&analyzer, "コンピューターとYoin"),
+                   vec!["コンピュータ", "と", "yoin"]);
+        assert_eq!(analyze(&analyzer, "ルーター"), vec!["ルータ"]);
+        assert_eq!(analyze(&analyzer, "コーヒー"), vec!["コーヒ"]);
+        assert_eq!(analyze(&analyzer, "キー"), vec!["キー"]);
+    }
+
+    #[test]
+    fn test_char_filters() {
+        let analyzer = Analyzer::new(Tokenizer::new(sysdic::tests::sysdic()))
+            .with_char_filter(Normalizer::new())
+            .with_char_filter(Normalizer::new().with_rule("ABC", "すもも"))
+            .with_token_filter(ReadingFormFilter);
+        let input = "ＡＢＣのＤ";
+        let tokens = analyzer.analyze(input);
+        assert_eq!(tokens.iter().map(|t| t.surface.as_str()).collect::<Vec<_>>(),
+                   vec!["スモモ", "ノ", "D"]);
+        assert_eq!(tokens.iter().map(|t| &input[t.start..t.end]).collect::<Vec<_>>(),
+                   vec!["ＡＢＣ", "の", "Ｄ"]);
+        assert_eq!((tokens[2].char_start, tokens[2].char_end), (4, 5));
+    }
+}
@@ -0,0 +1,150 @@
+//! Bunsetsu (文節) chunking.
+//!
+//! A bunsetsu is a content word followed by function words: 助詞, 助動詞, 接尾 and 非自立 verbs
+//! and adjectives such as いる in 食べている. Consecutive nouns such as 関西 国際 空港 form one
+//! bunsetsu, 接頭詞 and opening brackets are attached to the following word, and the other
+//! symbols to the preceding one. The rules depend on IPADIC parts of speech.
+use std::ops::Range;
+
+use features::Ipadic;
+use tokenizer::Token;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bunsetsu {
+    /// the start in bytes
+    pub start: usize,
+    /// the end in bytes
+    pub end: usize,
+    /// the indices of the tokens
+    pub tokens: Range<usize>,
+    /// the index of the head token, the last content word in the bunsetsu
+    pub head: usize,
+}
+
+impl Bunsetsu {
+    /// The surface of the bunsetsu in the input.
+    pub fn surface<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.start..self.end]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Content,
+    Function,
+    /// 接頭詞 and opening brackets
+    Prefix,
+    /// symbols other than opening brackets
+    Symbol,
+}
+
+fn kind(features: &Ipadic) -> Kind {
+    match (features.pos1, features.pos2) {
+        (Some("助詞"), _) | (Some("助動詞"), _) | (_, Some("接尾")) => Kind::Function,
+        (Some("動詞"), Some("非自立")) | (Some("形容詞"), Some("非自立")) => Kind::Function,
+        (Some("接頭詞"), _) | (Some("記号"), Some("括弧開")) => Kind::Prefix,
+        (Some("記号"), _) => Kind::Symbol,
+        _ => Kind::Content,
+    }
+}
+
+/// The bunsetsu being built.
+struct Chunk {
+    first: usize,
+    head: Option<usize>,
+    /// whether function words or symbols follow the content word
+    closed: bool,
+}
+
+impl Chunk {
+    fn new(first: usize) -> Self {
+        Chunk {
+            first,
+            head: None,
+            closed: false,
+        }
+    }
+
+    fn finish(self, tokens: &[Token], last: usize) -> Bunsetsu {
+        Bunsetsu {
+            start: tokens[self.first].start(),
+            end: tokens[last].end(),
+            tokens: self.first..last + 1,
+            head: self.head.unwrap_or(last),
+        }
+    }
+}
+
+/// Groups tokens into bunsetsu.
+pub fn chunk(tokens: &[Token]) -> Vec<Bunsetsu> {
+    let mut bunsetsu = Vec::new();
+    let mut current: Option<Chunk> = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let features = token.features_as::<Ipadic>();
+        let kind = kind(&features);
+        let starts = match current {
+            None => true,
+            Some(ref chunk) => {
+                match kind {
+                    Kind::Prefix => chunk.head.is_some(),
+                    Kind::Content => {
+                        let compound_noun = features.pos1 == Some("名詞") &&
+                                            tokens[i - 1].features_as::<Ipadic>().pos1 ==
+                                            Some("名詞");
+                        chunk.head.is_some() && (chunk.closed || !compound_noun)
+                    }
+                    Kind::Function | Kind::Symbol => false,
+                }
+            }
+        };
+        if starts {
+            if let Some(chunk) = current.take() {
+                bunsetsu.push(chunk.finish(tokens, i - 1));
+            }
+            current = Some(Chunk::new(i));
+        }
+        let chunk = current.as_mut().unwrap();
+        match kind {
+            Kind::Content => chunk.head = Some(i),
+            Kind::Function if features.pos2 != Some("接尾") => chunk.closed = true,
+            Kind::Symbol => chunk.closed = true,
+            _ => {}
+        }
+    }
+    if let Some(chunk) = current {
+        bunsetsu.push(chunk.finish(tokens, tokens.len() - 1));
+    }
+    bunsetsu
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    #[test]
+    fn test_chunk() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "関西国際のすももも「もも」のうち。";
+        let tokens = tokenizer.tokenize(input);
+        let bunsetsu = chunk(&tokens);
+        assert_eq!(bunsetsu.iter().map(|b| b.surface(input)).collect::<Vec<_>>(),
+                   vec!["関西国際の", "すももも", "「もも」の", "うち。"]);
+        assert_eq!(bunsetsu.iter().map(|b| tokens[b.head].surface()).collect::<Vec<_>>(),
+                   vec!["国際", "すもも", "もも", "うち"]);
+        assert_eq!(bunsetsu[2].tokens, 5..9);
+        assert_eq!((bunsetsu[3].start, bunsetsu[3].end), (input.len() - 9, input.len()));
+
+        let input = "すももと書いた";
+        let tokens = tokenizer.tokenize(input);
+        assert_eq!(chunk(&tokens).iter().map(|b| b.surface(input)).collect::<Vec<_>>(),
+                   vec!["すももと", "書いた"]);
+        let input = "すももを食べている";
+        let tokens = tokenizer.tokenize(input);
+        assert_eq!(chunk(&tokens).iter().map(|b| b.surface(input)).collect::<Vec<_>>(),
+                   vec!["すももを", "食べている"]);
+        assert_eq!(tokens[chunk(&tokens)[1].head].surface(), "食べ");
+        assert_eq!(chunk(&tokenizer.tokenize("")), vec![]);
+    }
+}
@@ -0,0 +1,287 @@
+//! Merging consecutive nouns into compound nouns, the opposite of `Mode::Search`.
+//!
+//! Nouns are merged with the 接頭詞 before them and the 接尾 after them, e.g. お + すもも + さん.
+//! The merged tokens keep their components. `CompoundMerger` is also a token filter of
+//! `analysis`, which merges `TokenBuf`s into ones without components.
+use std::ops::Range;
+
+use analysis::TokenFilter;
+use features::{self, Ipadic, Schema};
+use tokenizer::{Token, TokenBuf};
+
+/// A compound noun, or a token not merged with others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compound<'a> {
+    pub surface: String,
+    /// the start in bytes
+    pub start: usize,
+    /// the end in bytes
+    pub end: usize,
+    /// the start in characters
+    pub char_start: usize,
+    /// the end in characters
+    pub char_end: usize,
+    /// the readings of the components joined, or `None` if any of them is not available
+    pub reading: Option<String>,
+    pub components: Vec<Token<'a>>,
+}
+
+impl<'a> Compound<'a> {
+    fn new(components: Vec<Token<'a>>) -> Self {
+        let first = &components[0];
+        let last = &components[components.len() - 1];
+        Compound {
+            surface: components.iter().map(|t| t.surface()).collect(),
+            start: first.start(),
+            end: last.end(),
+            char_start: first.char_start(),
+            char_end: last.char_end(),
+            reading: components.iter().map(|t| t.reading()).collect(),
+            components,
+        }
+    }
+
+    /// Whether the token is merged from several tokens.
+    pub fn is_compound(&self) -> bool {
+        self.components.len() > 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Prefix,
+    Noun,
+    Suffix,
+    Other,
+}
+
+/// Merges consecutive nouns. The nouns of `名詞,非自立` and `名詞,代名詞` are not merged by
+/// default.
+#[derive(Debug, Clone)]
+pub struct CompoundMerger {
+    prefixes: bool,
+    suffixes: bool,
+    excluded: Vec<String>,
+}
+
+impl Default for CompoundMerger {
+    fn default() -> Self {
+        CompoundMerger {
+            prefixes: true,
+            suffixes: true,
+            excluded: Vec::new(),
+        }
+        .with_excluded(["名詞,非自立", "名詞,代名詞"])
+    }
+}
+
+impl CompoundMerger {
+    pub fn new() -> Self {
+        CompoundMerger::default()
+    }
+
+    /// Merges 接頭詞 with the following nouns.
+    pub fn with_prefixes(mut self, enabled: bool) -> Self {
+        self.prefixes = enabled;
+        self
+    }
+
+    /// Merges 接尾 with the preceding nouns.
+    pub fn with_suffixes(mut self, enabled: bool) -> Self {
+        self.suffixes = enabled;
+        self
+    }
+
+    /// Sets parts of speech not to be merged, e.g. `名詞,数`. Each tag is a prefix of the
+    /// features separated by commas; see `features::matches`.
+    pub fn with_excluded<I, S>(mut self, tags: I) -> Self
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>
+    {
+        self.excluded = tags.into_iter().map(|tag| tag.as_ref().to_string()).collect();
+        self
+    }
+
+    fn kind<'a, I: Iterator<Item = &'a str> + Clone>(&self, fields: I) -> Kind {
+        let excluded = self.excluded.iter().any(|tag| features::matches(tag, fields.clone()));
+        let features = Ipadic::from_fields(fields);
+        match (features.pos1, features.pos2) {
+            _ if excluded => Kind::Other,
+            (Some("接頭詞"), _) if self.prefixes => Kind::Prefix,
+            (Some("名詞"), Some("接尾")) if self.suffixes => Kind::Suffix,
+            (Some("名詞"), Some("接尾")) => Kind::Other,
+            (Some("名詞"), _) => Kind::Noun,
+            _ => Kind::Other,
+        }
+    }
+
+    /// Merges tokens. Tokens not merged are returned as compounds with one component.
+    pub fn merge<'a>(&self, tokens: &[Token<'a>]) -> Vec<Compound<'a>> {
+        spans(tokens.iter().map(|t| self.kind(t.features())))
+            .into_iter()
+            .map(|span| Compound::new(tokens[span].to_vec()))
+            .collect()
+    }
+}
+
+/// Merges the tokens into one. Its parts of speech are of the last noun, and its base form,
+/// reading and pronunciation are those of the tokens joined, or `*` if any of them is missing.
+fn merge_bufs(tokens: &[TokenBuf], head: &TokenBuf) -> TokenBuf {
+    let first = &tokens[0];
+    let last = &tokens[tokens.len() - 1];
+    let mut features = head.features.clone();
+    for (i, feature) in features.iter_mut().enumerate().skip(6) {
+        let joined = tokens.iter()
+            .map(|t| t.features.get(i).and_then(|f| features::optional(f)))
+            .collect::<Option<String>>();
+        *feature = joined.unwrap_or_else(|| "*".to_string());
+    }
+    TokenBuf {
+        surface: tokens.iter().map(|t| t.surface.as_str()).collect(),
+        start: first.start,
+        end: last.end,
+        char_start: first.char_start,
+        char_end: last.char_end,
+        utf16_start: first.utf16_start,
+        utf16_end: last.utf16_end,
+        features,
+        left_id: first.left_id,
+        right_id: last.right_id,
+        weight: tokens.iter().fold(0i16, |w, t| w.saturating_add(t.weight)),
+        known: tokens.iter().all(|t| t.known),
+        source: head.source,
+        connection_cost: first.connection_cost,
+        cost: last.cost,
+    }
+}
+
+impl TokenFilter for CompoundMerger {
+    fn filter(&self, tokens: Vec<TokenBuf>) -> Vec<TokenBuf> {
+        let kinds = tokens.iter()
+            .map(|t| self.kind(t.features.iter().map(String::as_str)))
+            .collect::<Vec<_>>();
+        spans(kinds.iter().copied())
+            .into_iter()
+            .map(|span| if span.len() == 1 {
+                tokens[span.start].clone()
+            } else {
+                let head = span.clone().rev().find(|&i| kinds[i] == Kind::Noun).unwrap();
+                merge_bufs(&tokens[span], &tokens[head])
+            })
+            .collect()
+    }
+}
+
+/// Groups the tokens of the kinds into the spans of compounds and single tokens.
+fn spans<I: IntoIterator<Item = Kind>>(kinds: I) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    // the start of the group being merged
+    let mut start = 0;
+    let mut end = 0;
+    let mut has_noun = false;
+    for (i, kind) in kinds.into_iter().enumerate() {
+        end = i + 1;
+        let joins = match kind {
+            Kind::Prefix => !has_noun,
+            Kind::Noun => true,
+            Kind::Suffix => has_noun,
+            Kind::Other => false,
+        };
+        if !joins {
+            flush(start..i, has_noun, &mut spans);
+            start = i;
+            has_noun = false;
+        }
+        match kind {
+            Kind::Other | Kind::Suffix if !has_noun => {
+                spans.push(i..i + 1);
+                start = i + 1;
+            }
+            _ => has_noun |= kind == Kind::Noun,
+        }
+    }
+    flush(start..end, has_noun, &mut spans);
+    spans
+}
+
+/// Pushes the group as a compound, or its tokens one by one if it has only prefixes.
+fn flush(group: Range<usize>, has_noun: bool, spans: &mut Vec<Range<usize>>) {
+    if group.is_empty() {
+        return;
+    }
+    if has_noun {
+        spans.push(group);
+    } else {
+        spans.extend(group.map(|i| i..i + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use analysis::{Analyzer, ReadingFormFilter};
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    fn surfaces(compounds: &[Compound]) -> Vec<String> {
+        compounds.iter().map(|c| c.surface.clone()).collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "関西国際のおすももさんとうち";
+        let tokens = tokenizer.tokenize(input);
+        let compounds = CompoundMerger::new().merge(&tokens);
+        assert_eq!(surfaces(&compounds),
+                   vec!["関西国際", "の", "おすももさん", "と", "うち"]);
+        assert_eq!(compounds[0].reading, Some("カンサイコクサイ".to_string()));
+        assert_eq!(compounds[0].components, tokens[0..2].to_vec());
+        assert_eq!(&input[compounds[2].start..compounds[2].end], "おすももさん");
+        assert_eq!((compounds[2].char_start, compounds[2].char_end), (5, 11));
+        assert!(compounds[2].is_compound() && !compounds[1].is_compound());
+
+        let merger = CompoundMerger::new().with_prefixes(false).with_suffixes(false);
+        assert_eq!(surfaces(&merger.merge(&tokens)),
+                   vec!["関西国際", "の", "お", "すもも", "さん", "と", "うち"]);
+        let merger = CompoundMerger::new().with_excluded(Vec::<&str>::new());
+        assert_eq!(surfaces(&merger.merge(&tokenizer.tokenize("すももうち"))),
+                   vec!["すももうち"]);
+        assert_eq!(surfaces(&CompoundMerger::new().merge(&tokenizer.tokenize("さんお"))),
+                   vec!["さん", "お"]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "関西国際のおすももさんと鰯桃";
+        let tokens = CompoundMerger::new()
+            .filter(tokenizer.tokenize(input).iter().map(TokenBuf::from).collect());
+        assert_eq!(tokens.iter().map(|t| t.surface.as_str()).collect::<Vec<_>>(),
+                   vec!["関西国際", "の", "おすももさん", "と", "鰯桃"]);
+        assert_eq!(tokens[2].features,
+                   vec!["名詞", "一般", "*", "*", "*", "*", "おすももさん", "オスモモサン",
+                        "オスモモサン"]);
+        assert_eq!((tokens[2].start, tokens[2].char_start, tokens[2].char_end),
+                   (15, 5, 11));
+        assert_eq!(&input[tokens[4].start..tokens[4].end], "鰯桃");
+        assert!(!tokens[4].known && tokens[4].features_as::<Ipadic>().reading.is_none());
+
+        let analyzer = Analyzer::new(tokenizer)
+            .with_token_filter(CompoundMerger::new())
+            .with_token_filter(ReadingFormFilter);
+        assert_eq!(analyzer.analyze("関西国際のすもも")
+                       .into_iter()
+                       .map(|t| t.surface)
+                       .collect::<Vec<_>>(),
+                   vec!["カンサイコクサイ", "ノ", "スモモ"]);
+    }
+
+    #[test]
+    fn test_reading() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let compounds = CompoundMerger::new().merge(&tokenizer.tokenize("すもも鰯桃"));
+        assert_eq!(surfaces(&compounds), vec!["すもも鰯桃"]);
+        assert_eq!(compounds[0].reading, None);
+    }
+}
@@ -0,0 +1,239 @@
+//! Conjugation of verbs, adjectives and auxiliary verbs.
+//!
+//! IPADIC has an entry for each inflected form of a word, with its conjugation type (活用型) and
+//! form (活用形). `Conjugator` indexes those entries by their base forms to generate any form of
+//! a word, and rewrites sentences between the plain style (だ・である調) and the polite style
+//! (です・ます調).
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use dic::FstDic;
+use features::{self, Ipadic, Schema};
+use tokenizer::Token;
+
+/// An inflected entry of a dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inflection<'a> {
+    pub surface: &'a str,
+    pub weight: i16,
+    pub features: Ipadic<'a>,
+}
+
+pub struct Conjugator<'a> {
+    /// base form -> inflected entries
+    inflections: HashMap<&'a str, Vec<Inflection<'a>>>,
+}
+
+impl<'a> Conjugator<'a> {
+    /// Indexes the entries with conjugation types in the dictionary.
+    pub fn new<T: Borrow<[u8]>>(dic: &'a FstDic<T>) -> Self {
+        let mut inflections = HashMap::new();
+        for morph in dic.morphs() {
+            let features = Ipadic::from_fields(features::fields(morph.contents));
+            if let (Some(base_form), Some(_)) = (features.base_form, features.conjugation_type) {
+                inflections.entry(base_form).or_insert_with(Vec::new).push(Inflection {
+                    surface: morph.surface,
+                    weight: morph.weight,
+                    features,
+                });
+            }
+        }
+        Conjugator { inflections }
+    }
+
+    /// All the inflected entries of a word.
+    pub fn inflections(&self, base_form: &str, conjugation_type: &str) -> Vec<&Inflection<'a>> {
+        self.inflections
+            .get(base_form)
+            .map(|entries| {
+                entries.iter()
+                    .filter(|e| e.features.conjugation_type == Some(conjugation_type))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn find(&self,
+            base_form: &str,
+            conjugation_type: &str,
+            form: &str,
+            pos: Option<(&str, &str)>)
+            -> Option<&'a str> {
+        self.inflections(base_form, conjugation_type)
+            .into_iter()
+            .filter(|e| e.features.conjugation_form == Some(form))
+            .min_by_key(|e| {
+                let same_pos = pos.is_none_or(|(pos1, pos2)| {
+                    e.features.pos1 == Some(pos1) && e.features.pos2 == Some(pos2)
+                });
+                (!same_pos, e.weight)
+            })
+            .map(|e| e.surface)
+    }
+
+    /// Conjugates a word to the form, e.g. 書く of 五段・カ行イ音便 to 書か in 未然形. The cheapest
+    /// entry is chosen if there are several ones.
+    pub fn conjugate(&self, base_form: &str, conjugation_type: &str, form: &str) -> Option<&'a str> {
+        self.find(base_form, conjugation_type, form, None)
+    }
+
+    /// Conjugates the word of a token to the form, preferring entries with the same parts of
+    /// speech as the token.
+    pub fn conjugate_token(&self, token: &Token, form: &str) -> Option<&'a str> {
+        let features = token.features_as::<Ipadic>();
+        let base_form = features.base_form?;
+        let conjugation_type = features.conjugation_type?;
+        let pos = (features.pos1.unwrap_or("*"), features.pos2.unwrap_or("*"));
+        self.find(base_form, conjugation_type, form, Some(pos))
+    }
+
+    /// Rewrites the plain style into the polite style, e.g. 書いた to 書きました. Only the
+    /// predicates at the ends of sentences are rewritten.
+    pub fn to_polite(&self, tokens: &[Token]) -> String {
+        let mut text = String::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let features = tokens[i].features_as::<Ipadic>();
+            let next = conjugation(tokens, i + 1);
+            let rewritten = match (features.pos1, features.conjugation_form, next) {
+                (Some("動詞"), Some("基本形"), _) if is_end(tokens, i + 1) => {
+                    self.polite_verb(&tokens[i], "ます").map(|s| (s, 1))
+                }
+                (Some("動詞"), _, Some(("特殊・タ", _))) if is_end(tokens, i + 2) => {
+                    self.polite_verb(&tokens[i], "ました").map(|s| (s, 2))
+                }
+                (Some("動詞"), _, Some(("特殊・ナイ", "基本形"))) if is_end(tokens, i + 2) => {
+                    self.polite_verb(&tokens[i], "ません").map(|s| (s, 2))
+                }
+                (Some("形容詞"), Some("基本形"), _) if is_end(tokens, i + 1) => {
+                    Some((format!("{}です", tokens[i].surface()), 1))
+                }
+                (Some("助動詞"), Some("基本形"), _) if features.conjugation_type ==
+                                                       Some("特殊・ダ") &&
+                                                       is_end(tokens, i + 1) => {
+                    Some(("です".to_string(), 1))
+                }
+                _ => None,
+            };
+            let (s, consumed) = rewritten.unwrap_or_else(|| (tokens[i].surface().to_string(), 1));
+            text.push_str(&s);
+            i += consumed;
+        }
+        text
+    }
+
+    fn polite_verb(&self, token: &Token, suffix: &str) -> Option<String> {
+        self.conjugate_token(token, "連用形").map(|s| format!("{}{}", s, suffix))
+    }
+
+    /// Rewrites the polite style into the plain style, e.g. 書きました to 書いた. Only the
+    /// predicates at the ends of sentences are rewritten.
+    pub fn to_plain(&self, tokens: &[Token]) -> String {
+        let mut text = String::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let features = tokens[i].features_as::<Ipadic>();
+            let next = conjugation(tokens, i + 1);
+            let next2 = conjugation(tokens, i + 2);
+            let rewritten = match (features.pos1, features.conjugation_type, next, next2) {
+                (Some("動詞"), _, Some(("特殊・マス", "基本形")), _) if is_end(tokens, i + 2) => {
+                    self.conjugate_token(&tokens[i], "基本形").map(|s| (s.to_string(), 2))
+                }
+                (Some("動詞"), Some(t), Some(("特殊・マス", "連用形")), Some(("特殊・タ", _)))
+                    if is_end(tokens, i + 3) => {
+                    // verbs without 音便, such as 一段 and サ変, have no 連用タ接続 but 連用形.
+                    self.conjugate_token(&tokens[i], "連用タ接続")
+                        .or_else(|| self.conjugate_token(&tokens[i], "連用形"))
+                        .map(|s| (format!("{}{}", s, past_suffix(t)), 3))
+                }
+                (Some("動詞"), _, Some(("特殊・マス", "未然形")), Some(("不変化型", _)))
+                    if is_end(tokens, i + 3) => {
+                    self.conjugate_token(&tokens[i], "未然形").map(|s| (format!("{}ない", s), 3))
+                }
+                (Some("助動詞"), Some("特殊・デス"), _, _) if features.conjugation_form ==
+                                                             Some("基本形") &&
+                                                             is_end(tokens, i + 1) => {
+                    let after_adjective = i > 0 &&
+                                          tokens[i - 1].features_as::<Ipadic>().pos1 ==
+                                          Some("形容詞");
+                    let s = if after_adjective { "" } else { "だ" };
+                    Some((s.to_string(), 1))
+                }
+                _ => None,
+            };
+            let (s, consumed) = rewritten.unwrap_or_else(|| (tokens[i].surface().to_string(), 1));
+            text.push_str(&s);
+            i += consumed;
+        }
+        text
+    }
+}
+
+/// The conjugation type and form of the token at `i`.
+fn conjugation<'a>(tokens: &[Token<'a>], i: usize) -> Option<(&'a str, &'a str)> {
+    let features = tokens.get(i)?.features_as::<Ipadic>();
+    Some((features.conjugation_type?, features.conjugation_form?))
+}
+
+/// Whether a sentence ends before the token at `i`.
+fn is_end(tokens: &[Token], i: usize) -> bool {
+    match tokens.get(i) {
+        None => true,
+        Some(token) => {
+            let features = token.features_as::<Ipadic>();
+            features.pos1 == Some("記号") ||
+            (features.pos1 == Some("助詞") && features.pos2 == Some("終助詞"))
+        }
+    }
+}
+
+/// た or だ following 連用タ接続, e.g. 書いた and 読んだ.
+fn past_suffix(conjugation_type: &str) -> &'static str {
+    if ["ガ行", "ナ行", "バ行", "マ行"].iter().any(|row| conjugation_type.contains(row)) {
+        "だ"
+    } else {
+        "た"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    #[test]
+    fn test_conjugate() {
+        let sysdic = sysdic::tests::sysdic();
+        let conjugator = Conjugator::new(&sysdic.dic);
+        assert_eq!(conjugator.conjugate("書く", "五段・カ行イ音便", "未然形"), Some("書か"));
+        assert_eq!(conjugator.conjugate("書く", "五段・カ行イ音便", "連用タ接続"), Some("書い"));
+        assert_eq!(conjugator.conjugate("書く", "一段", "未然形"), None);
+        assert_eq!(conjugator.conjugate("すもも", "一段", "未然形"), None);
+        assert_eq!(conjugator.inflections("食べる", "一段").len(), 3);
+
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize("食べる");
+        assert_eq!(conjugator.conjugate_token(&tokens[0], "連用形"), Some("食べ"));
+        assert_eq!(conjugator.conjugate_token(&tokenizer.tokenize("すもも")[0], "連用形"),
+                   None);
+    }
+
+    #[test]
+    fn test_polite_and_plain() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let conjugator = Conjugator::new(&tokenizer.sysdic().dic);
+        let cases = [("書く。", "書きます。"),
+                     ("書いた", "書きました"),
+                     ("書かない", "書きません"),
+                     ("食べる", "食べます"),
+                     ("食べた", "食べました"),
+                     ("した", "しました"),
+                     ("すももだ。", "すももです。")];
+        for &(plain, polite) in &cases {
+            assert_eq!(conjugator.to_polite(&tokenizer.tokenize(plain)), polite);
+            assert_eq!(conjugator.to_plain(&tokenizer.tokenize(polite)), plain);
+        }
+        assert_eq!(conjugator.to_polite(&tokenizer.tokenize("書くすもも")), "書くすもも");
+    }
+}
@@ -44,6 +44,10 @@ pub struct Iter<'a> {
     iseq: &'a [u8],
     input: &'a [u8],
     len: usize,
+    /// the start of the current state
+    state: usize,
+    /// the output of a transition, which is accepted when the key ends
+    pending: Option<u32>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -56,19 +60,21 @@ impl<'a> Iter<'a> {
             iseq: iseq,
             input: input,
             len: 0,
+            state: 0,
+            pending: None,
         }
     }
 
     fn read_u16(&mut self) -> u16 {
         let from = self.iseq[self.pc..].as_ptr() as *const u16;
         self.pc += 2; // skip 16 bits
-        unsafe { *from }
+        unsafe { from.read_unaligned() }
     }
 
     fn read_u32(&mut self) -> u32 {
         let from = self.iseq[self.pc..].as_ptr() as *const u32;
         self.pc += 4; // skip 32 bits
-        unsafe { *from }
+        unsafe { from.read_unaligned() }
     }
 
     fn get_jump_offset(&mut self, jump_size: u8) -> usize {
@@ -92,6 +98,7 @@ impl<'a> Iter<'a> {
         }
         self.len += 1;
         self.pc += jump;
+        self.state = self.pc;
     }
 
     fn run_outjump(&mut self) -> Option<u32> {
@@ -107,6 +114,7 @@ impl<'a> Iter<'a> {
         self.len += 1;
         let n = self.read_u32();
         self.pc += jump - 4 as usize;
+        self.state = self.pc;
         Some(n)
     }
 }
@@ -118,7 +126,15 @@ impl<'a> Iterator for Iter<'a> {
         loop {
             let op = op::Op(self.iseq[self.pc]);
             match op.code() {
-                op::OPCODE_BREAK => return None,
+                op::OPCODE_BREAK => {
+                    // an output on a transition belongs to the only key under the transition.
+                    // The key ends at a state without transitions and state outputs, so the
+                    // output is not accepted if the input ends or diverges before it.
+                    if self.pc == self.state {
+                        return self.pending.take().map(Accept);
+                    }
+                    return None;
+                }
                 op::OPCODE_JUMP => {
                     if self.len >= self.input.len() {
                         return None;
@@ -129,9 +145,8 @@ impl<'a> Iterator for Iter<'a> {
                     if self.len >= self.input.len() {
                         return None;
                     }
-                    match self.run_outjump() {
-                        None => (),
-                        Some(n) => return Some(Accept(n)),
+                    if let Some(n) = self.run_outjump() {
+                        self.pending = Some(n);
                     }
                 }
                 op::OPCODE_ACCEPT_WITH => {
@@ -178,3 +193,15 @@ fn test_op() {
             .collect();
     assert_eq!(iseq.run_iter(b"feb'").collect::<HashSet<_>>(), expected);
 }
+
+#[test]
+fn test_run_prefix_only() {
+    let samples: Vec<(&[u8], u32)> = vec![(b"ab", 0), (b"abcd", 1), (b"b", 2), (b"bcd", 3)];
+    let iseq = Fst::build(samples);
+    assert_eq!(iseq.run(b"a"), vec![]);
+    assert_eq!(iseq.run(b"abc"), vec![Accept(0)]);
+    assert_eq!(iseq.run(b"abce"), vec![Accept(0)]);
+    assert_eq!(iseq.run(b"abcde"), vec![Accept(0), Accept(1)]);
+    assert_eq!(iseq.run(b"bce"), vec![Accept(2)]);
+    assert_eq!(iseq.run(b"bcd"), vec![Accept(2), Accept(3)]);
+}
@@ -35,6 +35,16 @@ impl<T: Borrow<[i16]>> Matrix<T> {
         self.encode::<W, NativeEndian>(w)
     }
 
+    /// The number of right context ids.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// The number of left context ids.
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
     pub fn row(&self, left_id: u16) -> &[i16] {
         let h = left_id as usize;
         let start = h * self.width as usize;
@@ -1,5 +1,8 @@
 use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
 use std::iter::Iterator;
+use std::path::Path;
 
 mod matrix;
 pub use self::matrix::Matrix;
@@ -58,6 +61,58 @@ impl FstDic<Vec<u8>> {
             fst: fst,
         }
     }
+
+    /// Builds a dictionary from lines in the CSV format of MeCab dictionaries:
+    /// `surface,left_id,right_id,weight,features...`. Empty lines are ignored.
+    pub fn from_csv<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut lines = Vec::new();
+        for line in reader.lines() {
+            lines.push(line?);
+        }
+        let mut morphs = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            match parse_csv_morph(line) {
+                Some(morph) => morphs.push(morph),
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("invalid entry at line {}: {}", i + 1, line)))
+                }
+            }
+        }
+        Ok(FstDic::build(&morphs))
+    }
+
+    /// Reads a dictionary from a CSV file. See `from_csv` for the format.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        FstDic::from_csv(BufReader::new(File::open(path)?))
+    }
+}
+
+fn parse_csv_morph(line: &str) -> Option<Morph<&str>> {
+    let mut cols = line.splitn(5, ',');
+    let surface = cols.next().filter(|s| !s.is_empty())?;
+    let left_id = cols.next()?.parse().ok()?;
+    let right_id = cols.next()?.parse().ok()?;
+    let weight = cols.next()?.parse().ok()?;
+    let contents = cols.next()?;
+    Some(Morph {
+        surface,
+        left_id,
+        right_id,
+        weight,
+        contents,
+    })
+}
+
+impl<T: Borrow<[u8]>> FstDic<T> {
+    /// Iterates over all the morphs in the order they are stored.
+    pub fn morphs(&self) -> Morphs<'_> {
+        Morphs { morph_bytes: self.morph_bytes.borrow() }
+    }
 }
 
 impl<'a, T: Borrow<[u8]>> Dic<'a> for FstDic<T> {
@@ -91,6 +146,23 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+pub struct Morphs<'a> {
+    morph_bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Morphs<'a> {
+    type Item = Morph<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.morph_bytes.is_empty() {
+            return None;
+        }
+        let morph = unsafe { Morph::decode(self.morph_bytes) };
+        self.morph_bytes = &self.morph_bytes[morph.encoded_len()..];
+        Some(morph)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +207,50 @@ mod tests {
                     result);
         }
     }
+
+    #[test]
+    fn test_lookup_partial_match() {
+        let morphs = vec![Morph {
+                              surface: "もも",
+                              left_id: 1,
+                              right_id: 1,
+                              weight: 1,
+                              contents: "contents 1",
+                          },
+                          Morph {
+                              surface: "も",
+                              left_id: 2,
+                              right_id: 2,
+                              weight: 2,
+                              contents: "contents 2",
+                          }];
+        let dict = FstDic::build(&morphs);
+        assert_eq!(dict.lookup_str("もの"), vec![morphs[1].clone()]);
+        assert_eq!(dict.lookup_str("も"), vec![morphs[1].clone()]);
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let csv = "すもも,1,2,-100,名詞,一般\r\n\nもも,3,4,200,名詞,一般,*\n";
+        let dict = FstDic::from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(dict.lookup_str("すもも"),
+                   vec![Morph {
+                            surface: "すもも",
+                            left_id: 1,
+                            right_id: 2,
+                            weight: -100,
+                            contents: "名詞,一般",
+                        }]);
+        assert_eq!(dict.lookup_str("もも")[0].contents, "名詞,一般,*");
+
+        let err = FstDic::from_csv("すもも,1,2,-100,名詞\nもも,x,4,200,名詞".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_morphs() {
+        let dict = FstDic::from_csv("すもも,1,2,-100,名詞\nもも,3,4,200,名詞,一般".as_bytes()).unwrap();
+        assert_eq!(dict.morphs().map(|m| (m.surface, m.contents)).collect::<Vec<_>>(),
+                   vec![("すもも", "名詞"), ("もも", "名詞,一般")]);
+    }
 }
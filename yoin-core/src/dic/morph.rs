@@ -32,24 +32,31 @@ impl<S: Borrow<str>> Morph<S> {
     pub fn encode_native<W: Write>(&self, w: W) -> io::Result<()> {
         self.encode::<W, NativeEndian>(w)
     }
+
+    /// The length of the encoded bytes.
+    pub fn encoded_len(&self) -> usize {
+        ::std::mem::size_of::<u32>() * 2 + ::std::mem::size_of::<u16>() * 3 +
+        self.surface.borrow().len() + self.contents.borrow().len()
+    }
 }
 
 impl<'a> Morph<&'a str> {
     pub unsafe fn decode(mut bs: &'a [u8]) -> Self {
+        // entries are not aligned in the buffer.
         let ptr = bs.as_ptr() as *const u32;
-        let surface_len = *ptr;
+        let surface_len = ptr.read_unaligned();
         bs = &bs[::std::mem::size_of::<u32>()..];
         let surface = ::std::str::from_utf8_unchecked(&bs[..surface_len as usize]);
         bs = &bs[surface_len as usize..];
 
         let ptr = bs.as_ptr() as *const u16;
-        let left_id = *ptr;
-        let right_id = *ptr.offset(1);
-        let weight = *(ptr.offset(2) as *const i16);
+        let left_id = ptr.read_unaligned();
+        let right_id = ptr.offset(1).read_unaligned();
+        let weight = (ptr.offset(2) as *const i16).read_unaligned();
         bs = &bs[::std::mem::size_of::<i16>() * 3..];
 
         let ptr = bs.as_ptr() as *const u32;
-        let contents_len = *ptr;
+        let contents_len = ptr.read_unaligned();
         bs = &bs[::std::mem::size_of::<u32>()..];
         let contents = ::std::str::from_utf8_unchecked(&bs[..contents_len as usize]);
 
@@ -88,4 +95,5 @@ fn test_encode_decode() {
     m.encode_native(&mut buf).unwrap();
     let m2 = unsafe { Morph::decode(&buf) };
     assert_eq!(m2, m);
+    assert_eq!(m.encoded_len(), buf.len());
 }
@@ -195,13 +195,14 @@ impl<'a> Entry<'a> {
     }
 
     pub unsafe fn decode(bs: &'a [u8]) -> Self {
+        // entries are not aligned in the buffer.
         let ptr = bs.as_ptr() as *const u16;
-        let left_id = *ptr;
-        let right_id = *ptr.offset(1);
+        let left_id = ptr.read_unaligned();
+        let right_id = ptr.offset(1).read_unaligned();
         let ptr = ptr.offset(2) as *const i16;
-        let weight = *ptr;
+        let weight = ptr.read_unaligned();
         let ptr = ptr.offset(1) as *const u32;
-        let len = *ptr;
+        let len = ptr.read_unaligned();
         let ptr = ptr.offset(1) as *const u8;
         let buf = ::std::slice::from_raw_parts(ptr, len as usize);
         let contents = ::std::str::from_utf8_unchecked(buf);
@@ -228,8 +229,44 @@ fn test_entry_encode() {
     assert_eq!(actual, e);
 }
 
+/// An iterator over the entries of a category, decoded on demand.
+#[derive(Debug, Clone)]
+pub struct Entries<'a> {
+    offsets: ::std::slice::Iter<'a, u32>,
+    entries: &'a [u8],
+}
+
+impl<'a> Entries<'a> {
+    fn new(offsets: &'a [u32], entries: &'a [u8]) -> Self {
+        Entries {
+            offsets: offsets.iter(),
+            entries,
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        self.offsets
+            .next()
+            .map(|&offset| unsafe { Entry::decode(&self.entries[offset as usize..]) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.offsets.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Entries<'a> {}
+
 pub trait UnknownDic: CharCategorize {
-    fn fetch_entries<'a>(&'a self, cate: CategoryId) -> Vec<Entry<'a>>;
+    fn entries<'a>(&'a self, cate: CategoryId) -> Entries<'a>;
+
+    fn fetch_entries<'a>(&'a self, cate: CategoryId) -> Vec<Entry<'a>> {
+        self.entries(cate).collect()
+    }
 }
 
 pub struct UnkDic {
@@ -251,15 +288,10 @@ impl CharCategorize for UnkDic {
 }
 
 impl UnknownDic for UnkDic {
-    fn fetch_entries<'a>(&'a self, cate: CategoryId) -> Vec<Entry<'a>> {
+    fn entries<'a>(&'a self, cate: CategoryId) -> Entries<'a> {
         let count = self.counts[cate as usize] as usize;
         let index = self.indices[cate as usize] as usize;
-        let offsets = &self.entry_offsets[index..index + count];
-        let mut results = Vec::with_capacity(count);
-        for &offset in offsets {
-            results.push(unsafe { Entry::decode(&self.entries[offset as usize..]) });
-        }
-        results
+        Entries::new(&self.entry_offsets[index..index + count], &self.entries)
     }
 }
 
@@ -356,16 +388,10 @@ impl<'a> CharCategorize for CompiledUnkDic<'a> {
 }
 
 impl<'a> UnknownDic for CompiledUnkDic<'a> {
-    fn fetch_entries<'b>(&'b self, cate: CategoryId) -> Vec<Entry<'b>> {
+    fn entries<'b>(&'b self, cate: CategoryId) -> Entries<'b> {
         let count = self.counts[cate as usize] as usize;
         let index = self.indices[cate as usize] as usize;
-        let offsets = &self.entry_offsets[index..index + count];
-        let mut results = Vec::with_capacity(count);
-        for &offset in offsets {
-            let e = unsafe { Entry::decode(&self.entries[offset as usize..]) };
-            results.push(e);
-        }
-        results
+        Entries::new(&self.entry_offsets[index..index + count], self.entries)
     }
 }
 
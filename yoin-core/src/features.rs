@@ -0,0 +1,174 @@
+//! Typed views of morph features.
+//!
+//! Features are stored as a CSV line like `名詞,一般,*,*,*,*,すもも,スモモ,スモモ`. Fields
+//! containing commas are quoted as in MeCab's dictionaries, e.g. `"1,000"`. `Schema` gives names
+//! to the fields of a dictionary; `Ipadic` is the one for IPADIC.
+
+/// Splits features into fields. The quotes around a quoted field are removed, while escaped
+/// quotes (`""`) inside are kept as they are.
+pub fn fields(contents: &str) -> Fields<'_> {
+    Fields { rest: Some(contents) }
+}
+
+/// An iterator over the fields of features.
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    rest: Option<&'a str>,
+}
+
+/// Finds the closing quote of a field starting with a quote.
+fn closing_quote(field: &str) -> Option<usize> {
+    let bytes = field.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        if bytes[i] == b'"' {
+            if bytes.get(i + 1) != Some(&b'"') {
+                return Some(i);
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    None
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.rest?;
+        if rest.starts_with('"') {
+            if let Some(end) = closing_quote(rest) {
+                let after = &rest[end + 1..];
+                if after.is_empty() {
+                    self.rest = None;
+                    return Some(&rest[1..end]);
+                }
+                if let Some(after) = after.strip_prefix(',') {
+                    self.rest = Some(after);
+                    return Some(&rest[1..end]);
+                }
+            }
+        }
+        // not quoted, or quoted incorrectly
+        match rest.find(',') {
+            Some(i) => {
+                self.rest = Some(&rest[i + 1..]);
+                Some(&rest[..i])
+            }
+            None => {
+                self.rest = None;
+                Some(rest)
+            }
+        }
+    }
+}
+
+/// Maps `*`, which means no value in MeCab's dictionaries, to `None`.
+pub fn optional(field: &str) -> Option<&str> {
+    if field == "*" { None } else { Some(field) }
+}
+
+/// Whether the features start with the parts of speech of the pattern, e.g. `助詞` or
+/// `助詞,格助詞`. The pattern is split into fields as features are, and `*` in it matches any
+/// field.
+pub fn matches<'a, I: IntoIterator<Item = &'a str>>(pattern: &str, features: I) -> bool {
+    let mut features = features.into_iter();
+    fields(pattern).all(|p| features.next().is_some_and(|f| p == "*" || p == f))
+}
+
+/// Named fields of the features of a dictionary.
+pub trait Schema<'a>: Sized {
+    fn from_fields<I: Iterator<Item = &'a str>>(fields: I) -> Self;
+}
+
+/// Features of IPADIC. Fields with `*` or missing ones, such as readings of unknown words, are
+/// `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Ipadic<'a> {
+    /// 品詞, e.g. 名詞
+    pub pos1: Option<&'a str>,
+    /// 品詞細分類1, e.g. 固有名詞
+    pub pos2: Option<&'a str>,
+    /// 品詞細分類2, e.g. 地域
+    pub pos3: Option<&'a str>,
+    /// 品詞細分類3, e.g. 一般
+    pub pos4: Option<&'a str>,
+    /// 活用型, e.g. 一段
+    pub conjugation_type: Option<&'a str>,
+    /// 活用形, e.g. 連用形
+    pub conjugation_form: Option<&'a str>,
+    /// 原形
+    pub base_form: Option<&'a str>,
+    /// 読み
+    pub reading: Option<&'a str>,
+    /// 発音
+    pub pronunciation: Option<&'a str>,
+}
+
+impl<'a> Schema<'a> for Ipadic<'a> {
+    fn from_fields<I: Iterator<Item = &'a str>>(fields: I) -> Self {
+        let mut values = [None; 9];
+        for (value, field) in values.iter_mut().zip(fields) {
+            *value = optional(field);
+        }
+        Ipadic {
+            pos1: values[0],
+            pos2: values[1],
+            pos3: values[2],
+            pos4: values[3],
+            conjugation_type: values[4],
+            conjugation_form: values[5],
+            base_form: values[6],
+            reading: values[7],
+            pronunciation: values[8],
+        }
+    }
+}
+
+impl<'a> Ipadic<'a> {
+    /// The parts of speech, e.g. `名詞,固有名詞,地域`, without `*`s.
+    pub fn pos(&self) -> Vec<&'a str> {
+        [self.pos1, self.pos2, self.pos3, self.pos4].iter().filter_map(|&p| p).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let split = |s| fields(s).collect::<Vec<_>>();
+        assert_eq!(split("名詞,数,*"), vec!["名詞", "数", "*"]);
+        assert_eq!(split(""), vec![""]);
+        assert_eq!(split("名詞,\"1,000\",*"), vec!["名詞", "1,000", "*"]);
+        assert_eq!(split("\"a\"\"b,c\""), vec!["a\"\"b,c"]);
+        assert_eq!(split("\"a\"b,c"), vec!["\"a\"b", "c"]);
+        assert_eq!(split("\"a,b"), vec!["\"a", "b"]);
+    }
+
+    #[test]
+    fn test_matches() {
+        let contents = "助詞,格助詞,一般,*,*,*,が,ガ,ガ";
+        assert!(matches("助詞", fields(contents)));
+        assert!(matches("助詞,*,一般", fields(contents)));
+        assert!(!matches("助詞,係助詞", fields(contents)));
+        assert!(!matches("助詞,格助詞,一般,*,*,*,が,ガ,ガ,*", fields(contents)));
+    }
+
+    #[test]
+    fn test_ipadic() {
+        let features = Ipadic::from_fields(fields("動詞,自立,*,*,一段,連用形,食べる,タベ,タベ"));
+        assert_eq!(features.pos(), vec!["動詞", "自立"]);
+        assert_eq!(features.conjugation_type, Some("一段"));
+        assert_eq!(features.conjugation_form, Some("連用形"));
+        assert_eq!(features.base_form, Some("食べる"));
+        assert_eq!(features.reading, Some("タベ"));
+
+        let features = Ipadic::from_fields(fields("名詞,固有名詞,組織,*,*,*,*"));
+        assert_eq!(features.pos4, None);
+        assert_eq!((features.base_form, features.reading, features.pronunciation),
+                   (None, None, None));
+    }
+}
@@ -0,0 +1,213 @@
+//! Furigana (ruby) annotations.
+//!
+//! Readings of tokens are aligned to the kanji parts of their surfaces, so that okurigana are
+//! not annotated: 食べる with タベル becomes 食(た)べる. `to_html` and `to_text` render the
+//! annotations.
+use reading;
+use tokenizer::Token;
+
+/// A part of a text with its reading in hiragana if it is annotated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ruby<'a> {
+    pub base: &'a str,
+    pub ruby: Option<String>,
+}
+
+fn is_kanji(ch: char) -> bool {
+    matches!(ch,
+             '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' |
+             '\u{20000}'..='\u{2FFFF}' | '々' | '〆' | 'ヶ')
+}
+
+/// Splits a surface into runs of kana and runs of the others, with whether they are kana.
+fn segments(surface: &str) -> Vec<(&str, bool)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut last = None;
+    for (i, ch) in surface.char_indices() {
+        let kana = reading::is_kana_char(ch);
+        if let Some(last) = last {
+            if last != kana {
+                segments.push((&surface[start..i], last));
+                start = i;
+            }
+        }
+        last = Some(kana);
+    }
+    if let Some(last) = last {
+        segments.push((&surface[start..], last));
+    }
+    segments
+}
+
+fn align_segments<'a>(segments: &[(&'a str, bool)],
+                      reading: &str,
+                      rubies: &mut Vec<Ruby<'a>>)
+                      -> bool {
+    let (&(base, kana), rest) = match segments.split_first() {
+        Some(first) => first,
+        None => return reading.is_empty(),
+    };
+    if kana {
+        let katakana = reading::to_katakana(base);
+        if !reading.starts_with(katakana.as_str()) {
+            return false;
+        }
+        rubies.push(Ruby { base, ruby: None });
+        if align_segments(rest, &reading[katakana.len()..], rubies) {
+            return true;
+        }
+        rubies.pop();
+        return false;
+    }
+    // tries the shortest reading first
+    for (i, ch) in reading.char_indices() {
+        let end = i + ch.len_utf8();
+        rubies.push(Ruby {
+            base,
+            ruby: Some(reading::to_hiragana(&reading[..end])),
+        });
+        if align_segments(rest, &reading[end..], rubies) {
+            return true;
+        }
+        rubies.pop();
+    }
+    false
+}
+
+/// Aligns a reading in katakana to the parts of the surface other than kana. Returns `None` if
+/// the kana in the surface do not appear in the reading.
+pub fn align<'a>(surface: &'a str, reading: &str) -> Option<Vec<Ruby<'a>>> {
+    let mut rubies = Vec::new();
+    if align_segments(&segments(surface), reading, &mut rubies) {
+        Some(rubies)
+    } else {
+        None
+    }
+}
+
+/// Annotates tokens containing kanji with their readings. Readings of unknown words are not
+/// available; use `Tokenizer::reading` and `align` to estimate them.
+pub fn annotate<'t>(tokens: &'t [Token]) -> Vec<Ruby<'t>> {
+    let mut rubies = Vec::new();
+    for token in tokens {
+        let surface = token.surface();
+        let reading = match token.reading() {
+            Some(ref r) if surface.chars().any(is_kanji) => r.to_string(),
+            _ => {
+                rubies.push(Ruby {
+                    base: surface,
+                    ruby: None,
+                });
+                continue;
+            }
+        };
+        match align(surface, &reading) {
+            Some(aligned) => rubies.extend(aligned),
+            None => {
+                rubies.push(Ruby {
+                    base: surface,
+                    ruby: Some(reading::to_hiragana(&reading)),
+                })
+            }
+        }
+    }
+    rubies
+}
+
+fn escape_html(s: &str, out: &mut String) {
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Renders annotations with HTML `<ruby>` elements, e.g. `<ruby>食<rt>た</rt></ruby>べる`.
+pub fn to_html(rubies: &[Ruby]) -> String {
+    let mut html = String::new();
+    for r in rubies {
+        match r.ruby {
+            Some(ref ruby) => {
+                html.push_str("<ruby>");
+                escape_html(r.base, &mut html);
+                html.push_str("<rt>");
+                escape_html(ruby, &mut html);
+                html.push_str("</rt></ruby>");
+            }
+            None => escape_html(r.base, &mut html),
+        }
+    }
+    html
+}
+
+/// Renders annotations in the plain text format of Aozora Bunko, e.g. `漢字《かんじ》`. A base is
+/// marked with `｜` if it is not clear where it starts.
+pub fn to_text(rubies: &[Ruby]) -> String {
+    let mut text = String::new();
+    for r in rubies {
+        if let Some(ref ruby) = r.ruby {
+            if !r.base.chars().all(is_kanji) || text.chars().last().is_some_and(is_kanji) {
+                text.push('｜');
+            }
+            text.push_str(r.base);
+            text.push('《');
+            text.push_str(ruby);
+            text.push('》');
+        } else {
+            text.push_str(r.base);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    fn ruby<'a>(base: &'a str, ruby: Option<&str>) -> Ruby<'a> {
+        Ruby {
+            base,
+            ruby: ruby.map(|r| r.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        assert_eq!(align("食べる", "タベル"),
+                   Some(vec![ruby("食", Some("た")), ruby("べる", None)]));
+        assert_eq!(align("取り扱い", "トリアツカイ"),
+                   Some(vec![ruby("取", Some("と")),
+                             ruby("り", None),
+                             ruby("扱", Some("あつか")),
+                             ruby("い", None)]));
+        assert_eq!(align("お茶", "オチャ"),
+                   Some(vec![ruby("お", None), ruby("茶", Some("ちゃ"))]));
+        assert_eq!(align("関西", "カンサイ"), Some(vec![ruby("関西", Some("かんさい"))]));
+        assert_eq!(align("関ヶ原", "セキガハラ"), Some(vec![ruby("関ヶ原", Some("せきがはら"))]));
+        assert_eq!(align("食べる", "クウ"), None);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize("関西国際空港のすももを食べる");
+        let rubies = annotate(&tokens);
+        assert_eq!(to_html(&rubies),
+                   "<ruby>関西国際空港<rt>かんさいこくさいくうこう</rt></ruby>のすももを\
+                    <ruby>食<rt>た</rt></ruby>べる");
+        assert_eq!(to_text(&rubies), "関西国際空港《かんさいこくさいくうこう》のすももを食《た》べる");
+
+        let tokens = tokenizer.tokenize("関西国際");
+        assert_eq!(to_text(&annotate(&tokens)), "関西《かんさい》国際《こくさい》");
+        assert_eq!(to_text(&[ruby("鰯", None), ruby("桃", Some("もも"))]), "鰯｜桃《もも》");
+        assert_eq!(to_text(&[ruby("ＡＢ", Some("えーびー"))]), "｜ＡＢ《えーびー》");
+        assert_eq!(to_html(&[ruby("<a>", None)]), "&lt;a&gt;");
+    }
+}
@@ -1,8 +1,22 @@
 //! A Japanese morphological analyzer written in pure Rust.
 extern crate byteorder;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 pub mod dic;
 pub mod sysdic;
 pub mod tokenizer;
+pub mod sentence;
+pub mod normalize;
+pub mod features;
+pub mod ud;
+pub mod reading;
+pub mod romaji;
+pub mod furigana;
+pub mod mora;
+pub mod conjugation;
+pub mod bunsetsu;
+pub mod compound;
+pub mod analysis;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
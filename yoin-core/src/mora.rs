@@ -0,0 +1,103 @@
+//! Mora and syllable counting from pronunciations.
+//!
+//! A small kana (ャ, ァ, ...) forms a mora with the preceding kana, while ッ, ン and ー are morae
+//! by themselves: キャッキャー has 4 morae, キャ ッ キャ ー. In syllables, ッ, ン and ー belong
+//! to the preceding syllable: キャッ キャー.
+use reading;
+use tokenizer::Token;
+
+fn is_small(ch: char) -> bool {
+    matches!(ch,
+             'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' | 'ャ' | 'ュ' | 'ョ' | 'ヮ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' |
+             'ぉ' | 'ゃ' | 'ゅ' | 'ょ' | 'ゎ')
+}
+
+fn is_special(ch: char) -> bool {
+    matches!(ch, 'ッ' | 'ン' | 'ー' | 'っ' | 'ん')
+}
+
+/// Splits kana into morae. Characters other than kana, such as punctuation, are ignored.
+pub fn morae(kana: &str) -> Vec<&str> {
+    let mut morae: Vec<&str> = Vec::new();
+    let mut last_end = None;
+    for (i, ch) in kana.char_indices() {
+        let end = i + ch.len_utf8();
+        if !reading::is_kana_char(ch) {
+            last_end = None;
+            continue;
+        }
+        match morae.last_mut() {
+            // merges a small kana into the preceding kana, in the same slice of `kana`
+            Some(last) if is_small(ch) && last_end == Some(i) && !last.ends_with(is_small) &&
+                          !last.ends_with(is_special) => {
+                *last = &kana[i - last.len()..end];
+            }
+            _ => morae.push(&kana[i..end]),
+        }
+        last_end = Some(end);
+    }
+    morae
+}
+
+/// Counts morae of kana.
+pub fn count(kana: &str) -> usize {
+    morae(kana).len()
+}
+
+/// Counts syllables of kana, where ッ, ン and ー do not make syllables by themselves.
+pub fn count_syllables(kana: &str) -> usize {
+    let morae = morae(kana);
+    let count = morae.iter().filter(|m| !m.chars().all(is_special)).count();
+    match morae.first() {
+        // ッ, ン or ー at the beginning
+        Some(m) if m.chars().all(is_special) => count + 1,
+        _ => count,
+    }
+}
+
+/// Counts morae of the pronunciation of a token. Returns `None` if the pronunciation is not
+/// available.
+pub fn count_token(token: &Token) -> Option<usize> {
+    token.pronunciation().map(|p| count(&p))
+}
+
+/// Counts morae of tokens, e.g. a sentence. Tokens without pronunciations are counted as 0.
+pub fn count_tokens(tokens: &[Token]) -> usize {
+    tokens.iter().filter_map(count_token).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    #[test]
+    fn test_morae() {
+        assert_eq!(morae("キャッキャー"), vec!["キャ", "ッ", "キャ", "ー"]);
+        assert_eq!(morae("トーキョー"), vec!["ト", "ー", "キョ", "ー"]);
+        assert_eq!(morae("シンブン。ファン"), vec!["シ", "ン", "ブ", "ン", "ファ", "ン"]);
+        assert_eq!(morae("ァ。ィ"), vec!["ァ", "ィ"]);
+        assert_eq!(count("ふるいけや"), 5);
+        assert_eq!(count("ガッコウ"), 4);
+        assert_eq!(count(""), 0);
+    }
+
+    #[test]
+    fn test_syllables() {
+        assert_eq!(count_syllables("キャッキャー"), 2);
+        assert_eq!(count_syllables("トーキョー"), 2);
+        assert_eq!(count_syllables("シンブン"), 2);
+        assert_eq!(count_syllables("ンー"), 1);
+    }
+
+    #[test]
+    fn test_count_tokens() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize("関西国際空港のすもも。");
+        assert_eq!(count_token(&tokens[0]), Some(12));
+        // カンサイコクサイクーコー + ノ + スモモ
+        assert_eq!(count_tokens(&tokens), 16);
+        assert_eq!(count_token(&tokenizer.tokenize("鰯")[0]), None);
+    }
+}
@@ -0,0 +1,275 @@
+//! Text normalization before tokenization.
+//!
+//! `Normalizer` folds character variants that miss in dictionary lookups, like NFKC does for
+//! widths: full-width ASCII to ASCII, half-width katakana to full-width, and some compatibility
+//! characters such as ① or ㈱. `Normalized` keeps the offsets of the original text so that tokens
+//! of the normalized text can point into the original.
+
+const HALFWIDTH_KATAKANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノ\
+                                  ハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+const HALFWIDTH_KATAKANA_FIRST: u32 = 0xFF61;
+const HALFWIDTH_VOICED_MARK: char = '\u{FF9E}';
+const HALFWIDTH_SEMI_VOICED_MARK: char = '\u{FF9F}';
+
+// (character, replacement)
+const COMPATIBILITY: &[(char, &str)] = &[('①', "1"), ('②', "2"), ('③', "3"), ('④', "4"),
+                                         ('⑤', "5"), ('⑥', "6"), ('⑦', "7"), ('⑧', "8"),
+                                         ('⑨', "9"), ('⑩', "10"), ('⑪', "11"), ('⑫', "12"),
+                                         ('⑬', "13"), ('⑭', "14"), ('⑮', "15"), ('⑯', "16"),
+                                         ('⑰', "17"), ('⑱', "18"), ('⑲', "19"), ('⑳', "20"),
+                                         ('Ⅰ', "I"), ('Ⅱ', "II"), ('Ⅲ', "III"), ('Ⅳ', "IV"),
+                                         ('Ⅴ', "V"), ('Ⅵ', "VI"), ('Ⅶ', "VII"), ('Ⅷ', "VIII"),
+                                         ('Ⅸ', "IX"), ('Ⅹ', "X"), ('㈱', "(株)"), ('㈲', "(有)"),
+                                         ('㈹', "(代)"), ('㍾', "明治"), ('㍽', "大正"),
+                                         ('㍼', "昭和"), ('㍻', "平成"), ('㌔', "キロ"),
+                                         ('㌘', "グラム"), ('㌢', "センチ"), ('㍍', "メートル"),
+                                         ('㍉', "ミリ"), ('㌧', "トン"), ('㌦', "ドル"),
+                                         ('㍑', "リットル"), ('㌫', "パーセント"), ('№', "No"),
+                                         ('℡', "TEL"), ('™', "TM")];
+
+fn voiced(ch: char) -> Option<char> {
+    match ch {
+        'ウ' => Some('ヴ'),
+        _ if "カキクケコサシスセソタチツテトハヒフヘホ".contains(ch) => {
+            ::std::char::from_u32(ch as u32 + 1)
+        }
+        _ => None,
+    }
+}
+
+fn semi_voiced(ch: char) -> Option<char> {
+    if "ハヒフヘホ".contains(ch) {
+        ::std::char::from_u32(ch as u32 + 2)
+    } else {
+        None
+    }
+}
+
+fn halfwidth_katakana(ch: char) -> Option<char> {
+    match ch {
+        '\u{FF61}'..='\u{FF9F}' => {
+            HALFWIDTH_KATAKANA.chars().nth((ch as u32 - HALFWIDTH_KATAKANA_FIRST) as usize)
+        }
+        _ => None,
+    }
+}
+
+fn fullwidth_ascii(ch: char) -> Option<char> {
+    match ch {
+        '\u{FF01}'..='\u{FF5E}' => ::std::char::from_u32(ch as u32 - 0xFEE0),
+        '\u{3000}' => Some(' '),
+        _ => None,
+    }
+}
+
+/// An offset in bytes, characters and UTF-16 code units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Offset {
+    pub byte: usize,
+    pub char: usize,
+    pub utf16: usize,
+}
+
+impl Offset {
+    fn advance(&mut self, s: &str) {
+        for ch in s.chars() {
+            self.byte += ch.len_utf8();
+            self.char += 1;
+            self.utf16 += ch.len_utf16();
+        }
+    }
+}
+
+/// A normalized text with the offsets of its original.
+#[derive(Debug, Clone)]
+pub struct Normalized<'a> {
+    original: &'a str,
+    text: String,
+    /// (the start in `text`, the start in `original`) of each replaced unit, in order.
+    units: Vec<(usize, Offset)>,
+    /// the end of `original`
+    end: Offset,
+}
+
+impl<'a> Normalized<'a> {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn original(&self) -> &'a str {
+        self.original
+    }
+
+    /// Maps a start position in bytes of the normalized text to the original. A position inside
+    /// a replacement is mapped to the start of its original.
+    pub fn original_start(&self, pos: usize) -> Offset {
+        if pos >= self.text.len() {
+            return self.end;
+        }
+        let i = self.units.partition_point(|&(start, _)| start <= pos);
+        self.units[i - 1].1
+    }
+
+    /// Maps an end position in bytes of the normalized text to the original. A position inside
+    /// a replacement is mapped to the end of its original.
+    pub fn original_end(&self, pos: usize) -> Offset {
+        let i = self.units.partition_point(|&(start, _)| start < pos);
+        match self.units.get(i) {
+            Some(&(_, offset)) => offset,
+            None => self.end,
+        }
+    }
+}
+
+/// Normalization rules. All the built-in rules are enabled by default.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    fullwidth_ascii: bool,
+    halfwidth_katakana: bool,
+    compatibility: bool,
+    rules: Vec<(String, String)>,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Normalizer {
+            fullwidth_ascii: true,
+            halfwidth_katakana: true,
+            compatibility: true,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Normalizer {
+    pub fn new() -> Self {
+        Normalizer::default()
+    }
+
+    /// Folds full-width ASCII characters (and the ideographic space) to ASCII.
+    pub fn with_fullwidth_ascii(mut self, enabled: bool) -> Self {
+        self.fullwidth_ascii = enabled;
+        self
+    }
+
+    /// Folds half-width katakana to full-width, composing voiced sound marks.
+    pub fn with_halfwidth_katakana(mut self, enabled: bool) -> Self {
+        self.halfwidth_katakana = enabled;
+        self
+    }
+
+    /// Decomposes compatibility characters such as circled numbers and squared words.
+    pub fn with_compatibility(mut self, enabled: bool) -> Self {
+        self.compatibility = enabled;
+        self
+    }
+
+    /// Adds a rule replacing `from` with `to`. Rules take precedence over the built-in ones,
+    /// and the longest one is applied if several rules match.
+    pub fn with_rule(mut self, from: &str, to: &str) -> Self {
+        if !from.is_empty() {
+            self.rules.push((from.to_string(), to.to_string()));
+        }
+        self
+    }
+
+    /// Finds the longest rule matching at the beginning of `input`.
+    fn find_rule(&self, input: &str) -> Option<&(String, String)> {
+        self.rules
+            .iter()
+            .filter(|(from, _)| input.starts_with(from.as_str()))
+            .max_by_key(|(from, _)| from.len())
+    }
+
+    pub fn normalize<'a>(&self, input: &'a str) -> Normalized<'a> {
+        let mut text = String::with_capacity(input.len());
+        let mut units = Vec::new();
+        let mut offset = Offset::default();
+        let mut rest = input;
+
+        while let Some(ch) = rest.chars().next() {
+            units.push((text.len(), offset));
+            let len = if let Some((from, to)) = self.find_rule(rest) {
+                text.push_str(to);
+                from.len()
+            } else if let Some(kana) = halfwidth_katakana(ch).filter(|_| self.halfwidth_katakana) {
+                let mark = rest[ch.len_utf8()..].chars().next();
+                let composed = match mark {
+                    Some(HALFWIDTH_VOICED_MARK) => voiced(kana),
+                    Some(HALFWIDTH_SEMI_VOICED_MARK) => semi_voiced(kana),
+                    _ => None,
+                };
+                match composed {
+                    Some(c) => {
+                        text.push(c);
+                        ch.len_utf8() + mark.unwrap().len_utf8()
+                    }
+                    None => {
+                        text.push(kana);
+                        ch.len_utf8()
+                    }
+                }
+            } else if let Some(c) = fullwidth_ascii(ch).filter(|_| self.fullwidth_ascii) {
+                text.push(c);
+                ch.len_utf8()
+            } else if let Some(&(_, s)) = COMPATIBILITY.iter()
+                .find(|&&(c, _)| c == ch)
+                .filter(|_| self.compatibility) {
+                text.push_str(s);
+                ch.len_utf8()
+            } else {
+                text.push(ch);
+                ch.len_utf8()
+            };
+            offset.advance(&rest[..len]);
+            rest = &rest[len..];
+        }
+
+        Normalized {
+            original: input,
+            text,
+            units,
+            end: offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = Normalizer::new();
+        assert_eq!(normalizer.normalize("ＡＢＣ　１２３！").text(), "ABC 123!");
+        assert_eq!(normalizer.normalize("ｶﾞｷﾞﾊﾟｳﾞｰｱﾞ").text(), "ガギパヴーア゛");
+        assert_eq!(normalizer.normalize("①㈱㌔").text(), "1(株)キロ");
+        assert_eq!(normalizer.normalize("すもも").text(), "すもも");
+
+        let normalizer = Normalizer::new()
+            .with_fullwidth_ascii(false)
+            .with_rule("〜", "ー")
+            .with_rule("ｽﾓﾓ", "すもも");
+        assert_eq!(normalizer.normalize("ＡＢ〜ｽﾓﾓｽ").text(), "ＡＢーすももス");
+    }
+
+    #[test]
+    fn test_offsets() {
+        let input = "ｶﾞ㈱\u{20B9F}ａ";
+        let normalized = Normalizer::new().normalize(input);
+        assert_eq!(normalized.text(), "ガ(株)\u{20B9F}a");
+        let offset = |byte, char, utf16| {
+            Offset { byte, char, utf16 }
+        };
+        // ガ
+        assert_eq!(normalized.original_start(0), offset(0, 0, 0));
+        assert_eq!(normalized.original_end(3), offset(6, 2, 2));
+        // inside (株)
+        assert_eq!(normalized.original_start(4), offset(6, 2, 2));
+        assert_eq!(normalized.original_end(4), offset(9, 3, 3));
+        // ａ
+        assert_eq!(normalized.original_start(12), offset(13, 4, 5));
+        assert_eq!(normalized.original_end(13), offset(input.len(), 5, 6));
+        assert_eq!(normalized.original_start(13), offset(input.len(), 5, 6));
+    }
+}
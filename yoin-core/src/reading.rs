@@ -0,0 +1,98 @@
+//! Reading estimation for unknown words.
+//!
+//! IPADIC has no readings for unknown words. A reading is estimated from the surface: kana are
+//! converted to katakana, and other characters such as kanji are looked up one by one in a
+//! dictionary.
+use dic::Dic;
+use features::{self, Ipadic, Schema};
+
+/// The distance from hiragana to katakana in the code points.
+const HIRAGANA_TO_KATAKANA: u32 = 0x60;
+
+/// Converts hiragana to katakana. Other characters are kept as they are.
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|ch| match ch {
+            '\u{3041}'..='\u{3096}' | 'ゝ' | 'ゞ' => {
+                ::std::char::from_u32(ch as u32 + HIRAGANA_TO_KATAKANA).unwrap_or(ch)
+            }
+            _ => ch,
+        })
+        .collect()
+}
+
+/// Converts katakana to hiragana. Other characters are kept as they are.
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|ch| match ch {
+            '\u{30A1}'..='\u{30F6}' | 'ヽ' | 'ヾ' => {
+                ::std::char::from_u32(ch as u32 - HIRAGANA_TO_KATAKANA).unwrap_or(ch)
+            }
+            _ => ch,
+        })
+        .collect()
+}
+
+/// Whether the character is hiragana or katakana. ヶ is not, since it stands for 箇 and is
+/// read カ, ガ or コ.
+pub fn is_kana_char(ch: char) -> bool {
+    matches!(ch,
+             '\u{3041}'..='\u{3096}' | 'ゝ' | 'ゞ' | '\u{30A1}'..='\u{30F5}' |
+             '\u{30F7}'..='\u{30FA}' | 'ー' | 'ヽ' | 'ヾ')
+}
+
+/// Whether the string consists of hiragana and katakana only.
+pub fn is_kana(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_kana_char)
+}
+
+/// Estimates the reading in katakana of a surface consisting of kana only.
+pub fn estimate(surface: &str) -> Option<String> {
+    if is_kana(surface) {
+        Some(to_katakana(surface))
+    } else {
+        None
+    }
+}
+
+/// Estimates the reading in katakana of a surface, looking up non-kana characters one by one in
+/// the dictionary. The cheapest entry with a reading is used for each character. Returns `None`
+/// if any character is not found.
+pub fn estimate_with_dic<'a, D: Dic<'a>>(dic: &'a D, surface: &'a str) -> Option<String> {
+    let mut reading = String::new();
+    for (i, ch) in surface.char_indices() {
+        if is_kana_char(ch) {
+            reading.push_str(&to_katakana(&surface[i..i + ch.len_utf8()]));
+            continue;
+        }
+        let s = &surface[i..i + ch.len_utf8()];
+        let (_, r) = dic.lookup_str_iter(s)
+            .filter(|m| m.surface == s)
+            .filter_map(|m| {
+                Ipadic::from_fields(features::fields(m.contents)).reading.map(|r| (m.weight, r))
+            })
+            .min_by_key(|&(weight, _)| weight)?;
+        reading.push_str(r);
+    }
+    Some(reading)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(to_katakana("すもゝゔぁー"), "スモヽヴァー");
+        assert_eq!(to_hiragana("スモヽヴァー"), "すもゝゔぁー");
+        assert_eq!(estimate("ぴよピヨー"), Some("ピヨピヨー".to_string()));
+        assert_eq!(estimate("ぴよ子"), None);
+        assert_eq!(estimate("ヶ"), None);
+        assert_eq!(estimate(""), None);
+
+        let dic = sysdic::tests::sysdic().dic;
+        assert_eq!(estimate_with_dic(&dic, "桃と李"), Some("モモトスモモ".to_string()));
+        assert_eq!(estimate_with_dic(&dic, "桃鰯"), None);
+    }
+}
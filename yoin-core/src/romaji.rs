@@ -0,0 +1,314 @@
+//! Romanization of readings and pronunciations in kana.
+//!
+//! Long vowels marked with ー are written with macrons in Hepburn (`tōkyō`) and circumflexes in
+//! Kunrei-shiki and Nihon-shiki (`tôkyô`), or by repeating the vowel in ASCII (`tookyoo`).
+use reading;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum System {
+    #[default]
+    Hepburn,
+    Kunrei,
+    NihonShiki,
+}
+
+fn base(ch: char, system: System) -> Option<&'static str> {
+    let r = match (ch, system) {
+        ('シ', System::Hepburn) => "shi",
+        ('シ', _) => "si",
+        ('ジ', System::Hepburn) => "ji",
+        ('ジ', _) => "zi",
+        ('チ', System::Hepburn) => "chi",
+        ('チ', _) => "ti",
+        ('ヂ', System::Hepburn) => "ji",
+        ('ヂ', System::Kunrei) => "zi",
+        ('ヂ', System::NihonShiki) => "di",
+        ('ツ', System::Hepburn) => "tsu",
+        ('ツ', _) => "tu",
+        ('ヅ', System::NihonShiki) => "du",
+        ('ヅ', _) => "zu",
+        ('フ', System::Hepburn) => "fu",
+        ('フ', _) => "hu",
+        ('ヲ', System::NihonShiki) => "wo",
+        ('ヲ', _) => "o",
+        ('ヰ', System::NihonShiki) => "wi",
+        ('ヰ', _) => "i",
+        ('ヱ', System::NihonShiki) => "we",
+        ('ヱ', _) => "e",
+        _ => {
+            return match ch {
+                'ア' | 'ァ' => Some("a"),
+                'イ' | 'ィ' => Some("i"),
+                'ウ' | 'ゥ' => Some("u"),
+                'エ' | 'ェ' => Some("e"),
+                'オ' | 'ォ' => Some("o"),
+                'カ' | 'ヵ' => Some("ka"),
+                'キ' => Some("ki"),
+                'ク' => Some("ku"),
+                'ケ' | 'ヶ' => Some("ke"),
+                'コ' => Some("ko"),
+                'ガ' => Some("ga"),
+                'ギ' => Some("gi"),
+                'グ' => Some("gu"),
+                'ゲ' => Some("ge"),
+                'ゴ' => Some("go"),
+                'サ' => Some("sa"),
+                'ス' => Some("su"),
+                'セ' => Some("se"),
+                'ソ' => Some("so"),
+                'ザ' => Some("za"),
+                'ズ' => Some("zu"),
+                'ゼ' => Some("ze"),
+                'ゾ' => Some("zo"),
+                'タ' => Some("ta"),
+                'テ' => Some("te"),
+                'ト' => Some("to"),
+                'ダ' => Some("da"),
+                'デ' => Some("de"),
+                'ド' => Some("do"),
+                'ナ' => Some("na"),
+                'ニ' => Some("ni"),
+                'ヌ' => Some("nu"),
+                'ネ' => Some("ne"),
+                'ノ' => Some("no"),
+                'ハ' => Some("ha"),
+                'ヒ' => Some("hi"),
+                'ヘ' => Some("he"),
+                'ホ' => Some("ho"),
+                'バ' => Some("ba"),
+                'ビ' => Some("bi"),
+                'ブ' => Some("bu"),
+                'ベ' => Some("be"),
+                'ボ' => Some("bo"),
+                'パ' => Some("pa"),
+                'ピ' => Some("pi"),
+                'プ' => Some("pu"),
+                'ペ' => Some("pe"),
+                'ポ' => Some("po"),
+                'マ' => Some("ma"),
+                'ミ' => Some("mi"),
+                'ム' => Some("mu"),
+                'メ' => Some("me"),
+                'モ' => Some("mo"),
+                'ヤ' | 'ャ' => Some("ya"),
+                'ユ' | 'ュ' => Some("yu"),
+                'ヨ' | 'ョ' => Some("yo"),
+                'ラ' => Some("ra"),
+                'リ' => Some("ri"),
+                'ル' => Some("ru"),
+                'レ' => Some("re"),
+                'ロ' => Some("ro"),
+                'ワ' | 'ヮ' => Some("wa"),
+                'ヴ' => Some("vu"),
+                _ => None,
+            };
+        }
+    };
+    Some(r)
+}
+
+fn is_vowel(ch: char) -> bool {
+    matches!(ch, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+/// The vowel of a small kana following another kana, with whether it is a glide (ャュョ).
+fn small(ch: char) -> Option<(char, bool)> {
+    match ch {
+        'ャ' => Some(('a', true)),
+        'ュ' => Some(('u', true)),
+        'ョ' => Some(('o', true)),
+        'ァ' => Some(('a', false)),
+        'ィ' => Some(('i', false)),
+        'ゥ' => Some(('u', false)),
+        'ェ' => Some(('e', false)),
+        'ォ' => Some(('o', false)),
+        _ => None,
+    }
+}
+
+/// Combines a kana and a following small kana, e.g. キャ to `kya` and ファ to `fa`.
+fn combine(ch: char, r: &str, vowel: char, glide: bool, system: System) -> String {
+    let stem = match ch {
+        'ウ' if !glide => "w",
+        'フ' if !glide => "f",
+        'ツ' if !glide => "ts",
+        _ => &r[..r.len() - 1],
+    };
+    let palatal = r.ends_with('i') && (glide || vowel == 'e');
+    let mut s = stem.to_string();
+    if palatal || glide {
+        // シャ is `sha` in Hepburn, not `shya`.
+        let hepburn_palatal = system == System::Hepburn &&
+                              ((stem.len() > 1 && stem.ends_with('h')) || stem == "j");
+        if !hepburn_palatal {
+            s.push('y');
+        }
+    }
+    s.push(vowel);
+    s
+}
+
+enum Unit {
+    Mora(String),
+    Sokuon,
+    N,
+    Long,
+    Other(char),
+}
+
+fn units(kana: &str, system: System) -> Vec<Unit> {
+    let chars = reading::to_katakana(kana).chars().collect::<Vec<_>>();
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        i += 1;
+        let unit = match ch {
+            'ッ' => Unit::Sokuon,
+            'ン' => Unit::N,
+            'ー' => Unit::Long,
+            _ => {
+                match base(ch, system) {
+                    Some(r) => {
+                        match chars.get(i).and_then(|&c| small(c)).filter(|_| small(ch).is_none()) {
+                            Some((vowel, glide)) => {
+                                i += 1;
+                                Unit::Mora(combine(ch, r, vowel, glide, system))
+                            }
+                            None => Unit::Mora(r.to_string()),
+                        }
+                    }
+                    None => Unit::Other(ch),
+                }
+            }
+        };
+        units.push(unit);
+    }
+    units
+}
+
+fn long_vowel(vowel: char, system: System) -> char {
+    let (hepburn, circumflex) = match vowel {
+        'a' => ('ā', 'â'),
+        'i' => ('ī', 'î'),
+        'u' => ('ū', 'û'),
+        'e' => ('ē', 'ê'),
+        _ => ('ō', 'ô'),
+    };
+    if system == System::Hepburn { hepburn } else { circumflex }
+}
+
+/// Converts kana to romaji.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Romanizer {
+    system: System,
+    ascii: bool,
+}
+
+impl Romanizer {
+    pub fn new(system: System) -> Self {
+        Romanizer {
+            system,
+            ascii: false,
+        }
+    }
+
+    /// Writes long vowels by repeating the vowels instead of macrons or circumflexes.
+    pub fn with_ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Romanizes hiragana or katakana. Other characters are kept as they are.
+    pub fn romanize(&self, kana: &str) -> String {
+        let units = units(kana, self.system);
+        let mut s = String::new();
+        let mut sokuon = false;
+        for (i, unit) in units.iter().enumerate() {
+            match *unit {
+                Unit::Mora(ref r) => {
+                    if sokuon {
+                        if self.system == System::Hepburn && r.starts_with("ch") {
+                            s.push('t');
+                        } else if let Some(c) = r.chars().next().filter(|&c| !is_vowel(c)) {
+                            s.push(c);
+                        }
+                    }
+                    s.push_str(r);
+                }
+                Unit::Sokuon => {}
+                Unit::N => {
+                    s.push('n');
+                    if let Some(Unit::Mora(r)) = units.get(i + 1) {
+                        if r.starts_with(|c| is_vowel(c) || c == 'y') {
+                            s.push('\'');
+                        }
+                    }
+                }
+                Unit::Long => {
+                    if let Some(vowel) = s.chars().last().filter(|&c| is_vowel(c)) {
+                        if self.ascii {
+                            s.push(vowel);
+                        } else {
+                            s.pop();
+                            s.push(long_vowel(vowel, self.system));
+                        }
+                    }
+                }
+                Unit::Other(c) => s.push(c),
+            }
+            sokuon = matches!(*unit, Unit::Sokuon);
+        }
+        s
+    }
+}
+
+/// Romanizes hiragana or katakana with the system.
+pub fn romanize(kana: &str, system: System) -> String {
+    Romanizer::new(system).romanize(kana)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_romanize() {
+        let cases = [("トーキョー", "tōkyō", "tôkyô", "tôkyô"),
+                     ("シンブン", "shinbun", "sinbun", "sinbun"),
+                     ("チャヅケ", "chazuke", "tyazuke", "tyaduke"),
+                     ("ジュウ", "juu", "zyuu", "zyuu"),
+                     ("ヂャ", "ja", "zya", "dya"),
+                     ("ツヅミ", "tsuzumi", "tuzumi", "tudumi"),
+                     ("フジヲ", "fujio", "huzio", "huziwo"),
+                     ("ザッシ", "zasshi", "zassi", "zassi"),
+                     ("マッチャ", "matcha", "mattya", "mattya"),
+                     ("キンヨービ", "kin'yōbi", "kin'yôbi", "kin'yôbi"),
+                     ("ゲンイン", "gen'in", "gen'in", "gen'in"),
+                     ("ヒャク", "hyaku", "hyaku", "hyaku")];
+        for &(kana, hepburn, kunrei, nihon) in &cases {
+            assert_eq!(romanize(kana, System::Hepburn), hepburn);
+            assert_eq!(romanize(kana, System::Kunrei), kunrei);
+            assert_eq!(romanize(kana, System::NihonShiki), nihon);
+        }
+    }
+
+    #[test]
+    fn test_loanwords() {
+        let hepburn = |s| romanize(s, System::Hepburn);
+        assert_eq!(hepburn("ファイル"), "fairu");
+        assert_eq!(hepburn("ティーシャツ"), "tīshatsu");
+        assert_eq!(hepburn("ウィンドウ"), "windou");
+        assert_eq!(hepburn("シェア"), "shea");
+        assert_eq!(hepburn("ヴァイオリン"), "vaiorin");
+        assert_eq!(hepburn("ァ"), "a");
+    }
+
+    #[test]
+    fn test_others() {
+        let romanizer = Romanizer::new(System::Hepburn).with_ascii(true);
+        assert_eq!(romanizer.romanize("とうきょー"), "toukyoo");
+        assert_eq!(romanizer.romanize("ーあッ。ABC"), "a。ABC");
+        assert_eq!(romanizer.romanize("ンー"), "n");
+    }
+}
@@ -0,0 +1,199 @@
+//! Sentence boundary detection on top of tokens.
+//!
+//! A sentence ends at a terminator (句点, ！, ？ and so on) outside brackets, or at a line break.
+//! A quotation that ends with a terminator, such as 「はい。」, also ends the sentence if another
+//! quotation follows it.
+use dic::unknown::{CategoryId, CharCategorize};
+use tokenizer::Token;
+
+/// Whether `ch` opens a bracket or a quotation.
+pub fn is_opening(ch: char) -> bool {
+    matches!(ch, '「' | '『' | '（' | '(' | '【' | '〈' | '《' | '［' | '[' | '“')
+}
+
+/// Whether `ch` closes a bracket or a quotation.
+pub fn is_closing(ch: char) -> bool {
+    matches!(ch, '」' | '』' | '）' | ')' | '】' | '〉' | '》' | '］' | ']' | '”')
+}
+
+/// Whether `ch` terminates a sentence. Line breaks are not included.
+pub fn is_terminator(ch: char) -> bool {
+    matches!(ch, '。' | '．' | '！' | '？' | '!' | '?')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sentence<'a> {
+    start: usize,
+    text: &'a str,
+    tokens: Vec<Token<'a>>,
+}
+
+impl<'a> Sentence<'a> {
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// The start of the sentence in bytes.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The end of the sentence in bytes.
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    pub fn tokens(&self) -> &[Token<'a>] {
+        &self.tokens
+    }
+
+    pub fn into_tokens(self) -> Vec<Token<'a>> {
+        self.tokens
+    }
+}
+
+pub struct SentenceSplitter<'c, C: 'c> {
+    categories: &'c C,
+    space: CategoryId,
+    symbol: CategoryId,
+}
+
+impl<'c, C: CharCategorize> SentenceSplitter<'c, C> {
+    pub fn new(categories: &'c C) -> Self {
+        SentenceSplitter {
+            categories,
+            space: categories.category_id(' '),
+            symbol: categories.category_id('。'),
+        }
+    }
+
+    fn is_space(&self, token: &Token) -> bool {
+        token.surface().chars().all(|c| self.categories.category_id(c) == self.space)
+    }
+
+    fn is_symbol(&self, token: &Token) -> bool {
+        token.features().next() == Some("記号") ||
+        token.surface().chars().all(|c| self.categories.category_id(c) == self.symbol)
+    }
+
+    /// Groups the tokens of `input` into sentences. Spaces between sentences are dropped.
+    pub fn split<'a>(&self, input: &'a str, tokens: Vec<Token<'a>>) -> Vec<Sentence<'a>> {
+        let mut sentences = Vec::new();
+        let mut current: Vec<Token<'a>> = Vec::new();
+        // spaces are added to the current sentence only if another token follows them.
+        let mut spaces: Vec<Token<'a>> = Vec::new();
+        let mut depth = 0usize;
+        // a terminator outside brackets is found.
+        let mut terminated = false;
+        // a quotation ending with a terminator is closed.
+        let mut quote_terminated = false;
+        let mut after_terminator = false;
+
+        for token in tokens {
+            if self.is_space(&token) {
+                if token.surface().contains('\n') {
+                    spaces.clear();
+                    flush(input, &mut current, &mut sentences);
+                    depth = 0;
+                    terminated = false;
+                    quote_terminated = false;
+                    after_terminator = false;
+                } else if !current.is_empty() {
+                    spaces.push(token);
+                }
+                continue;
+            }
+
+            if !self.is_symbol(&token) {
+                if terminated {
+                    spaces.clear();
+                    flush(input, &mut current, &mut sentences);
+                }
+                terminated = false;
+                quote_terminated = false;
+                after_terminator = false;
+                current.append(&mut spaces);
+                current.push(token);
+                continue;
+            }
+
+            let is_period = token.features().nth(1) == Some("句点");
+            for ch in token.surface().chars() {
+                if is_opening(ch) {
+                    if depth == 0 && (terminated || quote_terminated) {
+                        spaces.clear();
+                        flush(input, &mut current, &mut sentences);
+                        terminated = false;
+                    }
+                    quote_terminated = false;
+                    depth += 1;
+                } else if is_closing(ch) {
+                    depth = depth.saturating_sub(1);
+                    quote_terminated = depth == 0 && after_terminator;
+                } else if is_terminator(ch) || is_period {
+                    if depth == 0 {
+                        terminated = true;
+                    }
+                } else if terminated {
+                    spaces.clear();
+                    flush(input, &mut current, &mut sentences);
+                    terminated = false;
+                }
+                after_terminator = is_terminator(ch) || is_period;
+            }
+            current.append(&mut spaces);
+            current.push(token);
+        }
+        flush(input, &mut current, &mut sentences);
+        sentences
+    }
+}
+
+fn flush<'a>(input: &'a str, tokens: &mut Vec<Token<'a>>, sentences: &mut Vec<Sentence<'a>>) {
+    if tokens.is_empty() {
+        return;
+    }
+    let tokens = ::std::mem::take(tokens);
+    let start = tokens[0].start();
+    let end = tokens[tokens.len() - 1].end();
+    sentences.push(Sentence {
+        start,
+        text: &input[start..end],
+        tokens,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    fn split(input: &str) -> Vec<String> {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let sentences = tokenizer.sentences(input);
+        for s in &sentences {
+            assert_eq!(&input[s.start()..s.end()], s.text());
+            assert_eq!(s.tokens().iter().map(|t| t.surface()).collect::<String>(),
+                       s.text());
+        }
+        sentences.iter().map(|s| s.text().to_string()).collect()
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("すもものうち。もものうち"),
+                   vec!["すもものうち。", "もものうち"]);
+        assert_eq!(split("すもも!? もものうち。\n\nうち"),
+                   vec!["すもも!?", "もものうち。", "うち"]);
+        assert_eq!(split("すもも\nもも"), vec!["すもも", "もも"]);
+    }
+
+    #[test]
+    fn test_split_brackets() {
+        assert_eq!(split("「すもも。もも。」とうち。"),
+                   vec!["「すもも。もも。」とうち。"]);
+        assert_eq!(split("「すもも。」「もも。」"), vec!["「すもも。」", "「もも。」"]);
+        assert_eq!(split("もも。「すもも」とうち。"),
+                   vec!["もも。", "「すもも」とうち。"]);
+    }
+}
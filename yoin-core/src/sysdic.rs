@@ -6,3 +6,186 @@ pub struct SysDic {
     pub matrix: Matrix<&'static [i16]>,
     pub unknown_dic: CompiledUnkDic<'static>,
 }
+
+/// A tiny IPADIC-like system dictionary for unit tests.
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use super::SysDic;
+    use dic::{FstDic, Matrix, Morph};
+    use dic::fst::Fst;
+    use dic::unknown::{Category, CharTable, CompiledUnkDic, Entry, UnkDic};
+
+    // context ids (0 is for BOS/EOS)
+    const NOUN: u16 = 1;
+    const PARTICLE: u16 = 2;
+    const SYMBOL: u16 = 3;
+
+    // (surface, context id, weight, contents)
+    const MORPHS: &[(&str, u16, i16, &str)] =
+        &[("す", NOUN, 3000, "名詞,一般,*,*,*,*,す,ス,ス"),
+          ("すもも", NOUN, 1000, "名詞,一般,*,*,*,*,すもも,スモモ,スモモ"),
+          ("もも", NOUN, 1000, "名詞,一般,*,*,*,*,もも,モモ,モモ"),
+          ("も", PARTICLE, 500, "助詞,係助詞,*,*,*,*,も,モ,モ"),
+          ("の", PARTICLE, 500, "助詞,連体化,*,*,*,*,の,ノ,ノ"),
+          ("うち", NOUN, 1000, "名詞,非自立,副詞可能,*,*,*,うち,ウチ,ウチ"),
+          ("関西", NOUN, 1500, "名詞,固有名詞,地域,一般,*,*,関西,カンサイ,カンサイ"),
+          ("国際", NOUN, 1500, "名詞,一般,*,*,*,*,国際,コクサイ,コクサイ"),
+          ("空港", NOUN, 1500, "名詞,一般,*,*,*,*,空港,クウコウ,クーコー"),
+          ("関西国際空港",
+           NOUN,
+           1000,
+           "名詞,固有名詞,組織,*,*,*,関西国際空港,カンサイコクサイクウコウ,カンサイコクサイクーコー"),
+          ("。", SYMBOL, 0, "記号,句点,*,*,*,*,。,。,。"),
+          ("「", SYMBOL, 0, "記号,括弧開,*,*,*,*,「,「,「"),
+          ("」", SYMBOL, 0, "記号,括弧閉,*,*,*,*,」,」,」"),
+          ("と", PARTICLE, 500, "助詞,格助詞,引用,*,*,*,と,ト,ト"),
+          ("桃", NOUN, 3000, "名詞,一般,*,*,*,*,桃,モモ,モモ"),
+          ("李", NOUN, 3000, "名詞,一般,*,*,*,*,李,スモモ,スモモ"),
+          ("食べる", NOUN, 2000, "動詞,自立,*,*,一段,基本形,食べる,タベル,タベル"),
+          ("食べ", NOUN, 2000, "動詞,自立,*,*,一段,連用形,食べる,タベ,タベ"),
+          ("食べ", NOUN, 2100, "動詞,自立,*,*,一段,未然形,食べる,タベ,タベ"),
+          ("書く", NOUN, 2000, "動詞,自立,*,*,五段・カ行イ音便,基本形,書く,カク,カク"),
+          ("書き", NOUN, 2000, "動詞,自立,*,*,五段・カ行イ音便,連用形,書く,カキ,カキ"),
+          ("書い", NOUN, 2000, "動詞,自立,*,*,五段・カ行イ音便,連用タ接続,書く,カイ,カイ"),
+          ("書か", NOUN, 2000, "動詞,自立,*,*,五段・カ行イ音便,未然形,書く,カカ,カカ"),
+          ("する", NOUN, 2000, "動詞,自立,*,*,サ変・スル,基本形,する,スル,スル"),
+          ("し", NOUN, 2000, "動詞,自立,*,*,サ変・スル,連用形,する,シ,シ"),
+          ("いる", NOUN, 1000, "動詞,非自立,*,*,一段,基本形,いる,イル,イル"),
+          ("て", PARTICLE, 500, "助詞,接続助詞,*,*,*,*,て,テ,テ"),
+          ("を", PARTICLE, 500, "助詞,格助詞,一般,*,*,*,を,ヲ,ヲ"),
+          ("ます", PARTICLE, 500, "助動詞,*,*,*,特殊・マス,基本形,ます,マス,マス"),
+          ("まし", PARTICLE, 500, "助動詞,*,*,*,特殊・マス,連用形,ます,マシ,マシ"),
+          ("ませ", PARTICLE, 500, "助動詞,*,*,*,特殊・マス,未然形,ます,マセ,マセ"),
+          ("ん", PARTICLE, 500, "助動詞,*,*,*,不変化型,基本形,ん,ン,ン"),
+          ("た", PARTICLE, 500, "助動詞,*,*,*,特殊・タ,基本形,た,タ,タ"),
+          ("ない", PARTICLE, 500, "助動詞,*,*,*,特殊・ナイ,基本形,ない,ナイ,ナイ"),
+          ("です", PARTICLE, 500, "助動詞,*,*,*,特殊・デス,基本形,です,デス,デス"),
+          ("だ", PARTICLE, 500, "助動詞,*,*,*,特殊・ダ,基本形,だ,ダ,ダ"),
+          ("お", NOUN, 1000, "接頭詞,名詞接続,*,*,*,*,お,オ,オ"),
+          ("さん", NOUN, 1000, "名詞,接尾,人名,*,*,*,さん,サン,サン")];
+
+    // (name, invoke, group, length, contents of its unknown entry), in the same order as
+    // IPADIC's char.def.
+    const CATEGORIES: &[(&str, bool, bool, u8, &str)] =
+        &[("DEFAULT", false, true, 0, "記号,一般,*,*,*,*,*"),
+          ("SPACE", false, true, 0, "記号,空白,*,*,*,*,*"),
+          ("KANJI", false, false, 2, "名詞,一般,*,*,*,*,*"),
+          ("SYMBOL", true, true, 0, "記号,一般,*,*,*,*,*"),
+          ("NUMERIC", true, true, 0, "名詞,数,*,*,*,*,*"),
+          ("ALPHA", true, true, 0, "名詞,固有名詞,組織,*,*,*,*"),
+          ("HIRAGANA", false, true, 2, "名詞,一般,*,*,*,*,*"),
+          ("KATAKANA", true, true, 2, "名詞,一般,*,*,*,*,*")];
+
+    // (first, last, category id)
+    const CHAR_RANGES: &[(u32, u32, u8)] = &[(0x0009, 0x000D, 1),
+                                             (0x0020, 0x0020, 1),
+                                             (0x0021, 0x002F, 3),
+                                             (0x0030, 0x0039, 4),
+                                             (0x003A, 0x0040, 3),
+                                             (0x0041, 0x005A, 5),
+                                             (0x005B, 0x0060, 3),
+                                             (0x0061, 0x007A, 5),
+                                             (0x007B, 0x007E, 3),
+                                             (0x3000, 0x303F, 3),
+                                             (0x3041, 0x309F, 6),
+                                             (0x30A1, 0x30FF, 7),
+                                             (0x4E00, 0x9FFF, 2),
+                                             (0xFF01, 0xFF0F, 3),
+                                             (0xFF66, 0xFF9F, 7)];
+
+    fn leak<T>(v: Vec<T>) -> &'static [T] {
+        Box::leak(v.into_boxed_slice())
+    }
+
+    fn dictionary() -> FstDic<&'static [u8]> {
+        let mut morph_bytes = Vec::new();
+        let mut inputs = Vec::new();
+        for &(surface, id, weight, contents) in MORPHS {
+            inputs.push((surface.as_bytes(), morph_bytes.len() as u32));
+            let morph = Morph {
+                surface,
+                left_id: id,
+                right_id: id,
+                weight,
+                contents,
+            };
+            morph.encode_native(&mut morph_bytes).unwrap();
+        }
+        inputs.sort();
+        let bytecode = leak(Fst::build(inputs).bytecode().to_vec());
+        unsafe { FstDic::from_bytes(bytecode, leak(morph_bytes)) }
+    }
+
+    fn matrix() -> Matrix<&'static [i16]> {
+        let costs: [[i16; 4]; 4] = [// from BOS/EOS
+                                    [0, -100, 300, 0],
+                                    // from NOUN
+                                    [0, 500, -200, 0],
+                                    // from PARTICLE
+                                    [0, -300, 800, 200],
+                                    // from SYMBOL
+                                    [0, 0, 500, 200]];
+        let mut matrix = Matrix::with_zeros(4, 4);
+        for (right_id, row) in costs.iter().enumerate() {
+            for (left_id, &cost) in row.iter().enumerate() {
+                matrix[(right_id as u16, left_id as u16)] = cost;
+            }
+        }
+        let mut buf = Vec::new();
+        matrix.encode_native(&mut buf).unwrap();
+        unsafe { Matrix::decode(leak(buf)) }
+    }
+
+    fn unknown_dic() -> CompiledUnkDic<'static> {
+        let categories = CATEGORIES.iter()
+            .map(|&(_, invoke, group, length, _)| {
+                Category {
+                    invoke,
+                    group,
+                    length,
+                }
+            })
+            .collect();
+        let mut char_table = CharTable::new(0, categories);
+        for &(first, last, id) in CHAR_RANGES {
+            for ch in first..last + 1 {
+                char_table.set(ch as usize, id);
+            }
+        }
+        let mut entries = HashMap::new();
+        for (id, &(_, _, _, _, contents)) in CATEGORIES.iter().enumerate() {
+            let context_id = if contents.starts_with("記号") { SYMBOL } else { NOUN };
+            entries.insert(id as u8,
+                           vec![Entry {
+                                    left_id: context_id,
+                                    right_id: context_id,
+                                    weight: 4000,
+                                    contents,
+                                }]);
+        }
+        let mut buf = Vec::new();
+        UnkDic::build(entries, char_table).encode_native(&mut buf).unwrap();
+        unsafe { CompiledUnkDic::decode(leak(buf)) }
+    }
+
+    /// Builds the test dictionary. Its buffers are leaked to get `'static` lifetimes.
+    pub fn sysdic() -> SysDic {
+        SysDic {
+            dic: dictionary(),
+            matrix: matrix(),
+            unknown_dic: unknown_dic(),
+        }
+    }
+
+    #[test]
+    fn test_shareable() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SysDic>();
+        assert_send_sync::<FstDic<&'static [u8]>>();
+        assert_send_sync::<FstDic<Vec<u8>>>();
+        assert_send_sync::<Matrix<&'static [i16]>>();
+        assert_send_sync::<CompiledUnkDic<'static>>();
+    }
+}
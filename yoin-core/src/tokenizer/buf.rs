@@ -0,0 +1,123 @@
+//! Tokens owning their data.
+//!
+//! `Token` borrows the input and the dictionaries. `TokenBuf` copies them so that tokens can be
+//! stored, sent to other threads, or serialized with the `serde` feature.
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use features::Schema;
+use super::{Source, Token};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TokenBuf {
+    pub surface: String,
+    /// the start in bytes
+    pub start: usize,
+    /// the end in bytes
+    pub end: usize,
+    /// the start in characters
+    pub char_start: usize,
+    /// the end in characters
+    pub char_end: usize,
+    /// the start in UTF-16 code units
+    pub utf16_start: usize,
+    /// the end in UTF-16 code units
+    pub utf16_end: usize,
+    pub features: Vec<String>,
+    pub left_id: u16,
+    pub right_id: u16,
+    /// the word cost
+    pub weight: i16,
+    /// false if the token is an unknown word.
+    pub known: bool,
+    pub source: Source,
+    /// the connection cost from the previous token
+    pub connection_cost: i64,
+    /// the cumulative cost of the path up to the token
+    pub cost: i64,
+}
+
+impl TokenBuf {
+    /// The features with named fields, e.g. `token.features_as::<Ipadic>()`.
+    pub fn features_as<'a, S: Schema<'a>>(&'a self) -> S {
+        S::from_fields(self.features.iter().map(String::as_str))
+    }
+}
+
+impl<'a, 'b> From<&'b Token<'a>> for TokenBuf {
+    fn from(token: &'b Token<'a>) -> Self {
+        TokenBuf {
+            surface: token.surface().to_string(),
+            start: token.start(),
+            end: token.end(),
+            char_start: token.char_start(),
+            char_end: token.char_end(),
+            utf16_start: token.utf16_start(),
+            utf16_end: token.utf16_end(),
+            features: token.features().map(|f| f.to_string()).collect(),
+            left_id: token.left_id(),
+            right_id: token.right_id(),
+            weight: token.weight(),
+            known: token.is_known(),
+            source: token.source(),
+            connection_cost: token.connection_cost(),
+            cost: token.cost(),
+        }
+    }
+}
+
+impl<'a> From<Token<'a>> for TokenBuf {
+    fn from(token: Token<'a>) -> Self {
+        TokenBuf::from(&token)
+    }
+}
+
+impl fmt::Display for TokenBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{}", self.surface, self.features.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    fn tokenize(input: &str) -> Vec<TokenBuf> {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize(input).iter().map(TokenBuf::from).collect();
+        tokens
+    }
+
+    #[test]
+    fn test_token_buf() {
+        let input = "ヨインのすもも";
+        let tokens = ::std::thread::spawn(move || tokenize(input)).join().unwrap();
+        assert_eq!(tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+                   vec!["ヨイン\t名詞,一般,*,*,*,*,*",
+                        "の\t助詞,連体化,*,*,*,*,の,ノ,ノ",
+                        "すもも\t名詞,一般,*,*,*,*,すもも,スモモ,スモモ"]);
+        assert_eq!((tokens[2].start, tokens[2].end), (12, 21));
+        assert_eq!((tokens[2].char_start, tokens[2].char_end), (4, 7));
+        assert!(!tokens[0].known && tokens[1].known);
+        assert_eq!((tokens[2].left_id, tokens[2].right_id, tokens[2].weight), (1, 1, 1000));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        extern crate serde_json;
+
+        let tokens = tokenize("すもものうち");
+        let json = serde_json::to_string(&tokens[1]).unwrap();
+        assert_eq!(json,
+                   r#"{"surface":"の","start":9,"end":12,"char_start":3,"char_end":4,"utf16_start":3,"utf16_end":4,"features":["助詞","連体化","*","*","*","*","の","ノ","ノ"],"left_id":2,"right_id":2,"weight":500,"known":true,"source":"SystemDic","connection_cost":-200,"cost":1200}"#);
+        assert_eq!(serde_json::from_str::<Vec<TokenBuf>>(&serde_json::to_string(&tokens).unwrap())
+                       .unwrap(),
+                   tokens);
+    }
+}
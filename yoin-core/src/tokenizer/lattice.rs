@@ -1,60 +1,247 @@
+//! The lattice of candidate morphemes built for an input.
+//!
+//! `Lattice` exposes every node considered by the Viterbi search together with its costs, which
+//! helps to find out why an input is split in an unexpected way.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
 use std::io::{self, Write};
 
 use dic::{Dic, Morph, FstDic};
 use dic::unknown::{UnknownDic, Entry, CharCategorize};
 use sysdic::SysDic;
+use features;
+use super::{Mode, Options};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where a word comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Source {
+    SystemDic,
+    UserDic,
+    /// estimated by the unknown word processing
+    Unknown,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeKind<'a> {
     BOS,
     EOS,
+    /// a word of the system dictionary
     Known(Morph<&'a str>),
+    /// a word of the user dictionary
+    User(Morph<&'a str>),
     Unknown(&'a str, Entry<'a>),
 }
 
 impl<'a> NodeKind<'a> {
-    fn left_id(&self) -> u16 {
+    pub fn left_id(&self) -> u16 {
         match *self {
             NodeKind::BOS | NodeKind::EOS => 0,
-            NodeKind::Known(ref morph) => morph.left_id,
+            NodeKind::Known(ref morph) | NodeKind::User(ref morph) => morph.left_id,
             NodeKind::Unknown(_, ref e) => e.left_id,
         }
     }
 
-    fn right_id(&self) -> u16 {
+    pub fn right_id(&self) -> u16 {
         match *self {
             NodeKind::BOS | NodeKind::EOS => 0,
-            NodeKind::Known(ref morph) => morph.right_id,
+            NodeKind::Known(ref morph) | NodeKind::User(ref morph) => morph.right_id,
             NodeKind::Unknown(_, ref e) => e.right_id,
         }
     }
 
-    fn weight(&self) -> i16 {
+    /// The word cost of the node.
+    pub fn weight(&self) -> i16 {
         match *self {
             NodeKind::BOS | NodeKind::EOS => 0,
-            NodeKind::Known(ref morph) => morph.weight,
+            NodeKind::Known(ref morph) | NodeKind::User(ref morph) => morph.weight,
             NodeKind::Unknown(_, ref e) => e.weight,
         }
     }
+
+    /// Whether the node is a word of the system or user dictionary.
+    pub fn is_known(&self) -> bool {
+        matches!(*self, NodeKind::Known(_) | NodeKind::User(_))
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(*self, NodeKind::Unknown(_, _))
+    }
+
+    /// `None` for BOS and EOS.
+    pub fn source(&self) -> Option<Source> {
+        match *self {
+            NodeKind::BOS | NodeKind::EOS => None,
+            NodeKind::Known(_) => Some(Source::SystemDic),
+            NodeKind::User(_) => Some(Source::UserDic),
+            NodeKind::Unknown(_, _) => Some(Source::Unknown),
+        }
+    }
 }
 
-type NodeId = usize;
+/// A constraint on the paths considered by the Viterbi search, like MeCab's partial parsing.
+///
+/// Offsets are in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint<'a> {
+    /// A token boundary must be at the offset.
+    Boundary(usize),
+    /// The span must be a single token. If `features` is given, the contents of the token must
+    /// match it field by field, where `*` matches any field; see `features::matches`.
+    ///
+    /// If no entry of the dictionaries matches, the token is an unknown word whose contents are
+    /// `features` itself: the fields it leaves out are missing and `*` is kept as is.
+    Token {
+        start: usize,
+        end: usize,
+        features: Option<&'a str>,
+    },
+}
+
+impl<'a> Constraint<'a> {
+    fn allows(&self, start: usize, end: usize, contents: &str) -> bool {
+        match *self {
+            Constraint::Boundary(b) => b <= start || end <= b,
+            Constraint::Token { start: s, end: e, features } => {
+                if end <= s || e <= start {
+                    return true;
+                }
+                let features_match = match features {
+                    Some(f) => features::matches(f, features::fields(contents)),
+                    None => true,
+                };
+                start == s && end == e && features_match
+            }
+        }
+    }
+}
+
+/// Constraints that no path can satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// The offset is out of the input or not on a character boundary.
+    InvalidOffset(usize),
+    /// The span of a token is empty.
+    EmptyToken { start: usize, end: usize },
+    /// The constraints at the indices contradict each other, e.g. a boundary inside a token or
+    /// tokens on the same span with incompatible features.
+    Conflict(usize, usize),
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConstraintError::InvalidOffset(offset) => {
+                write!(f, "offset {} is not on a character boundary of the input", offset)
+            }
+            ConstraintError::EmptyToken { start, end } => {
+                write!(f, "token {}..{} is empty", start, end)
+            }
+            ConstraintError::Conflict(i, j) => write!(f, "constraints {} and {} conflict", i, j),
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+/// Checks that every constraint is on character boundaries of the input and no constraints
+/// contradict each other, so that some path satisfies all of them.
+pub fn check_constraints(input: &str, constraints: &[Constraint]) -> Result<(), ConstraintError> {
+    let check_offset = |offset| if input.is_char_boundary(offset) {
+        Ok(())
+    } else {
+        Err(ConstraintError::InvalidOffset(offset))
+    };
+    for (i, c) in constraints.iter().enumerate() {
+        match *c {
+            Constraint::Boundary(b) => check_offset(b)?,
+            Constraint::Token { start, end, .. } => {
+                check_offset(start)?;
+                check_offset(end)?;
+                if start >= end {
+                    return Err(ConstraintError::EmptyToken { start, end });
+                }
+            }
+        }
+        for (j, d) in constraints[..i].iter().enumerate() {
+            let conflict = match (c, d) {
+                (&Constraint::Boundary(b), &Constraint::Token { start, end, .. }) |
+                (&Constraint::Token { start, end, .. }, &Constraint::Boundary(b)) => {
+                    start < b && b < end
+                }
+                (&Constraint::Token { start: s1, end: e1, features: f1 },
+                 &Constraint::Token { start: s2, end: e2, features: f2 }) => {
+                    s1 < e2 && s2 < e1 && ((s1, e1) != (s2, e2) || !compatible(f1, f2))
+                }
+                _ => false,
+            };
+            if conflict {
+                return Err(ConstraintError::Conflict(j, i));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the feature patterns of two tokens on the same span can be satisfied together,
+/// i.e. every field is the same in both or `*` in either.
+fn compatible(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            features::fields(a)
+                .zip(features::fields(b))
+                .all(|(x, y)| x == "*" || y == "*" || x == y)
+        }
+        _ => true,
+    }
+}
+
+/// The index of a node in `Lattice::nodes`.
+pub type NodeId = usize;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Node<'a> {
     pub start: usize,
+    /// the position of the node in characters
+    pub pos: usize,
+    /// the position of the node in UTF-16 code units
+    pub utf16_pos: usize,
     pub kind: NodeKind<'a>,
 }
 
 impl<'a> Node<'a> {
-    /// for debugging
-    #[allow(unused)]
-    fn surface(&self) -> &str {
+    /// The surface of the node. BOS and EOS have empty surfaces.
+    pub fn surface(&self) -> &'a str {
+        match self.kind {
+            NodeKind::BOS | NodeKind::EOS => "",
+            NodeKind::Known(ref m) | NodeKind::User(ref m) => m.surface,
+            NodeKind::Unknown(surface, _) => surface,
+        }
+    }
+
+    /// The end of the node in bytes.
+    pub fn end(&self) -> usize {
+        self.start + self.surface().len()
+    }
+
+    fn label(&self) -> &str {
         match self.kind {
             NodeKind::BOS => "BOS",
             NodeKind::EOS => "EOS",
-            NodeKind::Known(ref m) => m.surface,
-            NodeKind::Unknown(surface, _) => surface,
+            _ => self.surface(),
+        }
+    }
+
+    fn contents(&self) -> &'a str {
+        match self.kind {
+            NodeKind::BOS | NodeKind::EOS => "",
+            NodeKind::Known(ref m) | NodeKind::User(ref m) => m.contents,
+            NodeKind::Unknown(_, ref e) => e.contents,
         }
     }
 
@@ -62,7 +249,7 @@ impl<'a> Node<'a> {
         match self.kind {
             NodeKind::BOS => 0,
             NodeKind::EOS => 1,
-            NodeKind::Known(ref m) => m.surface.chars().count(),
+            NodeKind::Known(ref m) | NodeKind::User(ref m) => m.surface.chars().count(),
             NodeKind::Unknown(s, _) => s.chars().count(),
         }
     }
@@ -72,10 +259,6 @@ impl<'a> Node<'a> {
 struct NodeArena<'a>(Vec<Node<'a>>);
 
 impl<'a> NodeArena<'a> {
-    fn new() -> Self {
-        NodeArena(Vec::new())
-    }
-
     fn add(&mut self, node: Node<'a>) -> NodeId {
         let id = self.0.len();
         self.0.push(node);
@@ -89,6 +272,51 @@ impl<'a> NodeArena<'a> {
 
 const DUMMY_PREV_NODE: NodeId = !0;
 
+/// An iterator over the nodes on the best path in reverse order. See `Lattice::rev_best_path`.
+pub struct RevBestPath<'l> {
+    prev_table: &'l [NodeId],
+    p: NodeId,
+}
+
+impl<'l> Iterator for RevBestPath<'l> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        // BOS is 0.
+        if self.p == 0 || self.p == DUMMY_PREV_NODE {
+            return None;
+        }
+        let p = self.p;
+        self.p = self.prev_table[p];
+        Some(p)
+    }
+}
+
+/// Buffers of a lattice kept across inputs to avoid allocating them for each input.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    /// always empty. only the allocation is reused.
+    nodes: Vec<Node<'static>>,
+    end_nodes: Vec<Vec<NodeId>>,
+    prev_table: Vec<NodeId>,
+    cost_table: Vec<i64>,
+    path: Vec<NodeId>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Workspace::default()
+    }
+}
+
+/// Empties `nodes` and reuses its allocation for nodes of another lifetime.
+fn recycle<'a, 'b>(mut nodes: Vec<Node<'a>>) -> Vec<Node<'b>> {
+    nodes.clear();
+    let mut nodes = ::std::mem::ManuallyDrop::new(nodes);
+    // `Node<'a>` and `Node<'b>` differ only in lifetimes, and no element is left.
+    unsafe { Vec::from_raw_parts(nodes.as_mut_ptr().cast::<Node<'b>>(), 0, nodes.capacity()) }
+}
+
 #[derive(Clone)]
 pub struct Lattice<'a> {
     sdic: &'a SysDic,
@@ -96,18 +324,130 @@ pub struct Lattice<'a> {
     end_nodes: Vec<Vec<NodeId>>,
     prev_table: Vec<NodeId>,
     cost_table: Vec<i64>,
+    /// a buffer for `best_path_nodes`
+    path: Vec<NodeId>,
     pointer: usize,
+    /// `pointer` in UTF-16 code units
+    utf16_pointer: usize,
+    mode: Mode,
+    constraints: &'a [Constraint<'a>],
 }
 
 /// care about overflow...
 const MAX_COST: i64 = ::std::i32::MAX as i64;
 
+/// log(exp(x) + exp(y))
+fn log_add_exp(x: f64, y: f64) -> f64 {
+    if x == f64::NEG_INFINITY {
+        return y;
+    }
+    if y == f64::NEG_INFINITY {
+        return x;
+    }
+    let (max, min) = if x > y { (x, y) } else { (y, x) };
+    max + (min - max).exp().ln_1p()
+}
+
+fn is_kanji(ch: char) -> bool {
+    matches!(ch,
+             '\u{3005}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' |
+             '\u{F900}'..='\u{FAFF}')
+}
+
+/// Takes the first character off an unknown word node, and returns it as a node. Returns
+/// `None` for other nodes or if no character is left.
+fn split_first_char<'a>(node: &mut Node<'a>) -> Option<Node<'a>> {
+    let (surface, entry) = match node.kind {
+        NodeKind::Unknown(surface, ref entry) => (surface, entry.clone()),
+        _ => return None,
+    };
+    let ch = surface.chars().next()?;
+    let len = ch.len_utf8();
+    let first = Node {
+        start: node.start,
+        pos: node.pos,
+        utf16_pos: node.utf16_pos,
+        kind: NodeKind::Unknown(&surface[..len], entry.clone()),
+    };
+    node.start += len;
+    node.pos += 1;
+    node.utf16_pos += ch.len_utf16();
+    node.kind = NodeKind::Unknown(&surface[len..], entry);
+    Some(first)
+}
+
+/// An iterator over the nodes on a path. See `Lattice::path_nodes`.
+pub struct PathNodes<'l, 'a: 'l> {
+    lattice: &'l Lattice<'a>,
+    path: ::std::slice::Iter<'l, NodeId>,
+    prev: NodeId,
+    cost: i64,
+    /// the rest of the unknown word being split, with its id and costs
+    split: Option<(NodeId, Node<'a>, i64, i64)>,
+}
+
+impl<'l, 'a> Iterator for PathNodes<'l, 'a> {
+    type Item = (NodeId, Node<'a>, i64, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((id, ref mut rest, connection_cost, cost)) = self.split {
+            if let Some(node) = split_first_char(rest) {
+                return Some((id, node, connection_cost, cost));
+            }
+            self.split = None;
+        }
+        let id = *self.path.next()?;
+        let la = self.lattice;
+        let node = la.arena.get(id);
+        let connection_cost = la.connection_cost(self.prev, id);
+        self.cost += connection_cost + la.word_cost(node);
+        self.prev = id;
+        if la.mode == Mode::Extended && matches!(node.kind, NodeKind::Unknown(..)) {
+            self.split = Some((id, node.clone(), connection_cost, self.cost));
+            return self.next();
+        }
+        Some((id, node.clone(), connection_cost, self.cost))
+    }
+}
+
+const SEARCH_MODE_KANJI_LENGTH: usize = 2;
+const SEARCH_MODE_KANJI_PENALTY: i64 = 3000;
+const SEARCH_MODE_OTHER_LENGTH: usize = 7;
+const SEARCH_MODE_OTHER_PENALTY: i64 = 1700;
+
+/// The extra cost given to long words in the search mode, the same as Kuromoji's.
+fn search_mode_penalty(surface: &str) -> i64 {
+    let len = surface.chars().count();
+    if surface.chars().all(is_kanji) {
+        if len > SEARCH_MODE_KANJI_LENGTH {
+            return (len - SEARCH_MODE_KANJI_LENGTH) as i64 * SEARCH_MODE_KANJI_PENALTY;
+        }
+    } else if len > SEARCH_MODE_OTHER_LENGTH {
+        return (len - SEARCH_MODE_OTHER_LENGTH) as i64 * SEARCH_MODE_OTHER_PENALTY;
+    }
+    0
+}
+
 impl<'a> Lattice<'a> {
-    fn new(char_size: usize, sdic: &'a SysDic) -> Self {
-        let mut arena = NodeArena::new();
-        let mut end_nodes = vec![Vec::new(); char_size + 2];
+    fn new(workspace: Workspace,
+           char_size: usize,
+           sdic: &'a SysDic,
+           options: &Options<'a>)
+           -> Self {
+        let Workspace { nodes, mut end_nodes, mut prev_table, mut cost_table, path } = workspace;
+        let mut arena = NodeArena(recycle(nodes));
+        for ids in &mut end_nodes {
+            ids.clear();
+        }
+        end_nodes.resize(char_size + 2, Vec::new());
+        prev_table.clear();
+        prev_table.push(0);
+        cost_table.clear();
+        cost_table.push(0);
         let bos = arena.add(Node {
             start: 0,
+            pos: 0,
+            utf16_pos: 0,
             kind: NodeKind::BOS,
         });
         end_nodes[0].push(bos);
@@ -115,19 +455,37 @@ impl<'a> Lattice<'a> {
             sdic: sdic,
             arena: arena,
             end_nodes: end_nodes,
-            prev_table: vec![0],
-            cost_table: vec![0],
+            prev_table,
+            cost_table,
+            path,
             pointer: 0,
+            utf16_pointer: 0,
+            mode: options.mode(),
+            constraints: options.constraints(),
         }
     }
 
-    fn add(&mut self, start: usize, kind: NodeKind<'a>) {
-        let id = self.arena.add(Node {
+    /// Adds a node unless it violates the constraints, and returns whether it is added.
+    fn add(&mut self, start: usize, kind: NodeKind<'a>) -> bool {
+        let node = Node {
             start: start,
+            pos: self.pointer,
+            utf16_pos: self.utf16_pointer,
             kind: kind,
-        });
+        };
+        if node.kind != NodeKind::EOS &&
+           !self.constraints.iter().all(|c| c.allows(node.start, node.end(), node.contents())) {
+            return false;
+        }
+        self.push(node);
+        true
+    }
+
+    /// Adds a node without checking the constraints.
+    fn push(&mut self, node: Node<'a>) {
+        let id = self.arena.add(node);
         let node = self.arena.get(id);
-        let node_weight = node.kind.weight() as i64;
+        let node_weight = self.word_cost(node);
         let node_conn_row = self.sdic.matrix.row(node.kind.left_id());
         let mut node_prev = DUMMY_PREV_NODE;
         let mut node_cost = MAX_COST;
@@ -147,35 +505,82 @@ impl<'a> Lattice<'a> {
         self.end_nodes[self.pointer + node.surface_len()].push(id);
     }
 
+    /// The word cost of the node, including the penalty of the mode.
+    fn word_cost(&self, node: &Node) -> i64 {
+        let weight = node.kind.weight() as i64;
+        match self.mode {
+            Mode::Normal => weight,
+            Mode::Search | Mode::Extended => weight + search_mode_penalty(node.surface()),
+        }
+    }
+
+    fn connection_cost_between(&self, left: &Node, right: &Node) -> i64 {
+        self.sdic.matrix.row(right.kind.left_id())[left.kind.right_id() as usize] as i64
+    }
+
     fn forward(&mut self) -> usize {
         let old = self.pointer;
         self.pointer += 1;
-        while self.end_nodes[self.pointer].is_empty() {
+        // the last position is for EOS. positions no node reaches are skipped up to there.
+        let last = self.end_nodes.len() - 2;
+        while self.pointer < last && self.end_nodes[self.pointer].is_empty() {
             self.pointer += 1;
         }
         self.pointer - old
     }
 
-    fn end(&mut self) {
-        self.add(!0, NodeKind::EOS);
+    fn end(&mut self, input_len: usize) {
+        self.add(input_len, NodeKind::EOS);
+    }
+
+    pub fn build(input: &'a str, sdic: &'a SysDic, udic: Option<&'a FstDic<Vec<u8>>>) -> Self {
+        Lattice::build_unchecked(Workspace::new(), input, sdic, udic, &Options::new())
+    }
+
+    /// Builds the lattice with the options. With constraints, the lattice contains only the
+    /// paths satisfying all of them. Fails if the constraints are invalid; see
+    /// `check_constraints`.
+    pub fn build_with(input: &'a str,
+                      sdic: &'a SysDic,
+                      udic: Option<&'a FstDic<Vec<u8>>>,
+                      options: &Options<'a>)
+                      -> Result<Self, ConstraintError> {
+        Lattice::build_in(Workspace::new(), input, sdic, udic, options)
     }
 
-    pub fn build(input: &'a str, sdic: &'a SysDic, udic: Option<&'a FstDic<&'a [u8]>>) -> Self {
-        let mut la = Lattice::new(input.chars().count(), sdic);
+    /// Builds the lattice on the buffers of `workspace`. Take them back with `into_workspace`
+    /// to reuse them for the next input.
+    pub fn build_in(workspace: Workspace,
+                    input: &'a str,
+                    sdic: &'a SysDic,
+                    udic: Option<&'a FstDic<Vec<u8>>>,
+                    options: &Options<'a>)
+                    -> Result<Self, ConstraintError> {
+        check_constraints(input, options.constraints())?;
+        Ok(Lattice::build_unchecked(workspace, input, sdic, udic, options))
+    }
+
+    fn build_unchecked(workspace: Workspace,
+                       input: &'a str,
+                       sdic: &'a SysDic,
+                       udic: Option<&'a FstDic<Vec<u8>>>,
+                       options: &Options<'a>)
+                       -> Self {
+        let constraints = options.constraints();
+        let mut la = Lattice::new(workspace, input.chars().count(), sdic, options);
         let mut input_chars = input.chars();
         let mut byte_pos = 0;
 
         while !input_chars.as_str().is_empty() {
+            let first_id = la.arena.0.len();
             let mut is_matched = false;
             if let Some(udic) = udic {
                 for m in udic.lookup_str_iter(input_chars.as_str()) {
-                    is_matched = true;
-                    la.add(byte_pos, NodeKind::Known(m));
+                    is_matched |= la.add(byte_pos, NodeKind::User(m));
                 }
             }
             for m in sdic.dic.lookup_str_iter(input_chars.as_str()) {
-                is_matched = true;
-                la.add(byte_pos, NodeKind::Known(m));
+                is_matched |= la.add(byte_pos, NodeKind::Known(m));
             }
             let ch = input_chars.clone().next().unwrap();
             let category = sdic.unknown_dic.categorize(ch);
@@ -186,7 +591,7 @@ impl<'a> Lattice<'a> {
             if !is_matched || category.invoke {
                 let mut end = ch.len_utf8();
                 let mut word_len = 1;
-                let entries = sdic.unknown_dic.fetch_entries(cid);
+                let entries = sdic.unknown_dic.entries(cid);
                 if category.group {
                     while end < input_str.len() {
                         let c = match input_str[end..].chars().next() {
@@ -203,9 +608,10 @@ impl<'a> Lattice<'a> {
                             break;
                         }
                     }
+                    let end = la.cut_at_constraints(byte_pos, byte_pos + end) - byte_pos;
                     let surface = &input_str[..end];
-                    for e in entries.iter() {
-                        la.add(byte_pos, NodeKind::Unknown(surface, e.clone()));
+                    for e in entries.clone() {
+                        la.add(byte_pos, NodeKind::Unknown(surface, e));
                     }
                 }
                 if category.length > 0 {
@@ -222,73 +628,350 @@ impl<'a> Lattice<'a> {
                             }
                         }
                         let surface = &(input_chars.as_str())[..p];
-                        for e in entries.iter() {
-                            la.add(byte_pos, NodeKind::Unknown(surface, e.clone()));
+                        for e in entries.clone() {
+                            la.add(byte_pos, NodeKind::Unknown(surface, e));
                         }
                     }
                 }
             }
+            if !constraints.is_empty() {
+                la.add_forced_nodes(input, byte_pos, first_id);
+            }
             let cnt = la.forward();
             for _ in 0..cnt {
                 if let Some(c) = input_chars.next() {
                     byte_pos += c.len_utf8();
+                    la.utf16_pointer += c.len_utf16();
                 }
             }
         }
 
-        la.end();
+        la.end(byte_pos);
         la
     }
 
-    fn rev_output_path(&self) -> Vec<NodeId> {
-        if let Some(ref ps) = self.end_nodes.last() {
-            let mut path = Vec::new();
-            let mut p = ps[0];
-            debug_assert!(self.arena.get(p).kind == NodeKind::EOS);
-            // skip EOS node.
-            p = self.prev_table[p];
-            if p == DUMMY_PREV_NODE {
-                return Vec::new();
+    /// Shortens a group of unknown characters from `start` to `end` so that it ends at the
+    /// first boundary of the constraints inside it, like MeCab's partial parsing.
+    fn cut_at_constraints(&self, start: usize, end: usize) -> usize {
+        self.constraints
+            .iter()
+            .flat_map(|c| match *c {
+                Constraint::Boundary(b) => [b, b],
+                Constraint::Token { start: s, end: e, .. } => [s, e],
+            })
+            .filter(|&b| start < b && b < end)
+            .min()
+            .unwrap_or(end)
+    }
+
+    /// Adds nodes for the forced tokens starting at `start` that no dictionary provides, and
+    /// a single character node if no node starting at `start` satisfies the constraints.
+    fn add_forced_nodes(&mut self, input: &'a str, start: usize, first_id: NodeId) {
+        let unknown_dic = &self.sdic.unknown_dic;
+        for c in self.constraints {
+            let (end, features) = match *c {
+                Constraint::Token { start: s, end, features } if s == start => (end, features),
+                _ => continue,
+            };
+            if self.arena.0[first_id..].iter().any(|n| n.end() == end) {
+                continue;
+            }
+            let surface = &input[start..end];
+            let cid = unknown_dic.category_id(surface.chars().next().unwrap());
+            let mut added = false;
+            for e in unknown_dic.entries(cid) {
+                added |= self.add(start, NodeKind::Unknown(surface, e));
+            }
+            // no entry matches the features, or other tokens on the same span.
+            if !added {
+                if let Some(mut e) = unknown_dic.entries(cid).next() {
+                    if let Some(features) = features {
+                        e.contents = features;
+                    }
+                    self.push(Node {
+                        start,
+                        pos: self.pointer,
+                        utf16_pos: self.utf16_pointer,
+                        kind: NodeKind::Unknown(surface, e),
+                    });
+                }
+            }
+        }
+
+        if self.arena.0.len() == first_id {
+            let ch = input[start..].chars().next().unwrap();
+            let surface = &input[start..start + ch.len_utf8()];
+            let cid = unknown_dic.category_id(ch);
+            for e in unknown_dic.entries(cid) {
+                self.add(start, NodeKind::Unknown(surface, e));
             }
-            while p != 0 {
-                path.push(p);
-                p = self.prev_table[p];
+        }
+    }
+
+    /// All nodes in the lattice, including BOS (the first one) and EOS (the last one).
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.arena.0
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<'a> {
+        self.arena.get(id)
+    }
+
+    /// The minimum cost of the paths from BOS to the node, including the word cost of the node
+    /// (and the penalty of the mode).
+    /// Returns `None` if no path reaches the node.
+    pub fn cost(&self, id: NodeId) -> Option<i64> {
+        let cost = self.cost_table[id];
+        if cost >= MAX_COST { None } else { Some(cost) }
+    }
+
+    /// The predecessor of the node on the best path from BOS to the node.
+    pub fn best_prev(&self, id: NodeId) -> Option<NodeId> {
+        match self.prev_table[id] {
+            DUMMY_PREV_NODE => None,
+            _ if id == 0 => None,
+            prev => Some(prev),
+        }
+    }
+
+    /// The connection cost from the node `left` to the node `right`.
+    pub fn connection_cost(&self, left: NodeId, right: NodeId) -> i64 {
+        self.connection_cost_between(self.arena.get(left), self.arena.get(right))
+    }
+
+    /// The nodes on the best path, excluding BOS and EOS.
+    pub fn best_path(&self) -> Vec<NodeId> {
+        let mut path = self.rev_best_path().collect::<Vec<_>>();
+        path.reverse();
+        path
+    }
+
+    /// Iterates over the nodes on the best path from the last to the first without allocation.
+    pub fn rev_best_path(&self) -> RevBestPath<'_> {
+        let p = match self.end_nodes.last() {
+            Some(ps) if !ps.is_empty() => {
+                debug_assert!(self.arena.get(ps[0]).kind == NodeKind::EOS);
+                // skip EOS node.
+                self.prev_table[ps[0]]
             }
-            debug_assert!(self.arena.get(p).kind == NodeKind::BOS);
-            path
-        } else {
-            Vec::new()
+            _ => DUMMY_PREV_NODE,
+        };
+        RevBestPath {
+            prev_table: &self.prev_table,
+            p,
+        }
+    }
+
+    /// Releases the buffers of the lattice to build another one on them.
+    pub fn into_workspace(self) -> Workspace {
+        Workspace {
+            nodes: recycle(self.arena.0),
+            end_nodes: self.end_nodes,
+            prev_table: self.prev_table,
+            cost_table: self.cost_table,
+            path: self.path,
+        }
+    }
+
+    /// The nodes on `path` with their ids, the connection costs from their predecessors and
+    /// the cumulative costs of the path up to them. In the extended mode, unknown words are
+    /// split into characters, which have the ids and the costs of the original words.
+    pub fn path_nodes<'l>(&'l self, path: &'l [NodeId]) -> PathNodes<'l, 'a> {
+        PathNodes {
+            lattice: self,
+            path: path.iter(),
+            // BOS
+            prev: 0,
+            cost: 0,
+            split: None,
         }
     }
 
+    /// Same as `path_nodes` of `best_path`, but keeps the path in a buffer of the lattice,
+    /// which is reused through `Workspace`.
+    pub fn best_path_nodes(&mut self) -> PathNodes<'_, 'a> {
+        let mut path = ::std::mem::take(&mut self.path);
+        path.clear();
+        path.extend(self.rev_best_path());
+        path.reverse();
+        self.path = path;
+        self.path_nodes(&self.path)
+    }
+
     pub fn into_output(self) -> Vec<Node<'a>> {
-        let path = self.rev_output_path();
-        let NodeArena(mut nodes) = self.arena;
+        self.path_nodes(&self.best_path()).map(|(_, node, _, _)| node).collect()
+    }
+
+    /// Enumerates the `n` best paths in ascending order of their total costs.
+    ///
+    /// This runs A* search from EOS to BOS, using the costs computed by the forward Viterbi
+    /// pass as the (exact) heuristic. Each path excludes BOS and EOS.
+    pub fn nbest_paths(&self, n: usize) -> Vec<(Vec<NodeId>, i64)> {
+        let eos = match self.end_nodes.last() {
+            Some(ps) if !ps.is_empty() && n > 0 => ps[0],
+            _ => return Vec::new(),
+        };
+        // hypotheses[i] = (node, the index of the hypothesis for its successor)
+        let mut hypotheses = vec![(eos, DUMMY_PREV_NODE)];
+        let mut queue = BinaryHeap::new();
+        queue.push((Reverse(self.cost_table[eos]), 0, 0));
         let mut results = Vec::new();
-        for p in path {
-            results.push(nodes.swap_remove(p));
+
+        while let Some((Reverse(estimate), cost, index)) = queue.pop() {
+            let (id, _) = hypotheses[index];
+            if id == 0 {
+                let mut path = Vec::new();
+                let mut p = hypotheses[index].1;
+                while hypotheses[p].0 != eos {
+                    path.push(hypotheses[p].0);
+                    p = hypotheses[p].1;
+                }
+                results.push((path, estimate));
+                if results.len() >= n {
+                    break;
+                }
+                continue;
+            }
+            let node = self.arena.get(id);
+            let node_cost = cost + self.word_cost(node);
+            for &prev_id in &self.end_nodes[node.pos] {
+                if self.cost_table[prev_id] >= MAX_COST {
+                    continue;
+                }
+                let prev_cost = node_cost +
+                                self.connection_cost_between(self.arena.get(prev_id), node);
+                hypotheses.push((prev_id, index));
+                queue.push((Reverse(prev_cost + self.cost_table[prev_id]),
+                            prev_cost,
+                            hypotheses.len() - 1));
+            }
         }
-        results.reverse();
         results
     }
 
-    /// for debugging
-    #[allow(unused)]
-    fn dump_dot<W: Write>(&self, mut w: W) -> io::Result<()> {
+    /// Computes the marginal probability of every node by the forward-backward algorithm.
+    ///
+    /// The probability of a path is proportional to `exp(-cost / temperature)`, so higher
+    /// temperatures flatten the distribution. The result is indexed by `NodeId`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `temperature` is not positive.
+    pub fn marginals(&self, temperature: f64) -> Vec<f64> {
+        assert!(temperature > 0.0, "temperature must be positive: {}", temperature);
+        let n = self.arena.0.len();
+        let theta = 1.0 / temperature;
+        let edge = |prev: NodeId, id: NodeId| {
+            let node = self.arena.get(id);
+            let cost = self.connection_cost_between(self.arena.get(prev), node) +
+                       self.word_cost(node);
+            -(cost as f64) * theta
+        };
+
+        // nodes are added in order of their positions, so predecessors have smaller ids.
+        let mut alpha = vec![f64::NEG_INFINITY; n];
+        alpha[0] = 0.0;
+        for id in 1..n {
+            for &prev in &self.end_nodes[self.arena.get(id).pos] {
+                alpha[id] = log_add_exp(alpha[id], alpha[prev] + edge(prev, id));
+            }
+        }
+        let mut beta = vec![f64::NEG_INFINITY; n];
+        beta[n - 1] = 0.0;
+        for id in (1..n).rev() {
+            for &prev in &self.end_nodes[self.arena.get(id).pos] {
+                beta[prev] = log_add_exp(beta[prev], beta[id] + edge(prev, id));
+            }
+        }
+
+        let z = alpha[n - 1];
+        alpha.iter().zip(beta).map(|(a, b)| (a + b - z).exp()).collect()
+    }
+
+    /// Writes the best predecessor of each node in the DOT language.
+    pub fn dump_dot<W: Write>(&self, mut w: W) -> io::Result<()> {
         writeln!(w, "digraph lattice {{")?;
         writeln!(w, "\trankdir=LR;")?;
         writeln!(w, "\tnode [shape=circle]")?;
-        for (id, &prev_id) in self.prev_table.iter().enumerate() {
-            if prev_id != DUMMY_PREV_NODE {
+        for id in 0..self.prev_table.len() {
+            if let Some(prev_id) = self.best_prev(id) {
                 writeln!(w,
                          "\t\"{}\"[label=\"{}\"];",
                          id,
-                         self.arena.get(id).surface())?;
+                         self.arena.get(id).label())?;
                 let prev_node = self.arena.get(prev_id);
-                writeln!(w, "\t\"{}\"[label=\"{}\"];", prev_id, prev_node.surface())?;
+                writeln!(w, "\t\"{}\"[label=\"{}\"];", prev_id, prev_node.label())?;
                 writeln!(w, "\t\"{}\" -> \"{}\";", prev_id, id)?;
             }
         }
         writeln!(w, "}}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+
+    #[test]
+    fn test_inspect() {
+        let sdic = sysdic::tests::sysdic();
+        let input = "すもももももももものうち";
+        let la = Lattice::build(input, &sdic, None);
+        let nodes = la.nodes();
+        assert_eq!(nodes[0].kind, NodeKind::BOS);
+        assert_eq!(nodes[nodes.len() - 1].kind, NodeKind::EOS);
+        assert_eq!(nodes[nodes.len() - 1].start, input.len());
+
+        let path = la.best_path();
+        let surfaces = path.iter().map(|&id| la.node(id).surface()).collect::<Vec<_>>();
+        assert_eq!(surfaces, vec!["すもも", "も", "もも", "も", "もも", "の", "うち"]);
+
+        // the accumulated costs are consistent with the best predecessors.
+        let mut prev = 0;
+        for &id in &path {
+            assert_eq!(la.best_prev(id), Some(prev));
+            let node = la.node(id);
+            assert_eq!(&input[node.start..node.end()], node.surface());
+            assert!(node.kind.is_known());
+            let expected = la.cost(prev).unwrap() + la.connection_cost(prev, id) +
+                           node.kind.weight() as i64;
+            assert_eq!(la.cost(id), Some(expected));
+            prev = id;
+        }
+        assert_eq!(la.best_prev(0), None);
+    }
+
+    #[test]
+    fn test_marginals() {
+        let sdic = sysdic::tests::sysdic();
+        let input = "すもももももももものうち";
+        let la = Lattice::build(input, &sdic, None);
+        let marginals = la.marginals(1000.0);
+        assert!((marginals[0] - 1.0).abs() < 1e-9);
+        assert!((marginals[marginals.len() - 1] - 1.0).abs() < 1e-9);
+        // every character is covered by exactly one node on each path.
+        for (i, _) in input.char_indices() {
+            let sum: f64 = la.nodes()
+                .iter()
+                .zip(&marginals)
+                .filter(|&(node, _)| node.start <= i && i < node.end())
+                .map(|(_, &p)| p)
+                .sum();
+            assert!((sum - 1.0).abs() < 1e-9, "sum at {} is {}", i, sum);
+        }
+
+        // the best path dominates at low temperature.
+        let marginals = la.marginals(1.0);
+        for id in la.best_path() {
+            assert!(marginals[id] > 0.99);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "temperature must be positive")]
+    fn test_marginals_zero_temperature() {
+        let sdic = sysdic::tests::sysdic();
+        Lattice::build("すもも", &sdic, None).marginals(0.0);
+    }
+}
@@ -1,32 +1,75 @@
+use std::borrow::Cow;
 use std::iter::Iterator;
-use std::str::Split;
 use std::fmt;
+use std::io::{self, BufRead};
+use std::path::Path as FilePath;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
-mod lattice;
-use self::lattice::{Lattice, Node, NodeKind};
+pub mod lattice;
+use self::lattice::{check_constraints, Lattice, Node, NodeId, NodeKind};
+pub use self::lattice::{Constraint, ConstraintError, Source, Workspace};
+
+pub mod stream;
+pub use self::stream::TokenStream;
+
+pub mod buf;
+pub use self::buf::TokenBuf;
 use sysdic::SysDic;
 use dic::FstDic;
+use sentence::{Sentence, SentenceSplitter};
+use normalize::Normalized;
+use features::{self, Fields, Ipadic, Schema};
+use ud::{self, Upos};
+use reading;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token<'a> {
     start: usize,
+    end: usize,
+    /// the start in characters
+    pos: usize,
+    char_end: usize,
+    /// the start in UTF-16 code units
+    utf16_pos: usize,
+    utf16_end: usize,
     surface: &'a str,
     contents: &'a str,
+    left_id: u16,
+    right_id: u16,
+    weight: i16,
+    source: Source,
+    connection_cost: i64,
+    cost: i64,
 }
 
 impl<'a> Token<'a> {
-    fn new(node: Node<'a>) -> Self {
-        let Node { start, kind } = node;
+    fn new(node: Node<'a>, connection_cost: i64, cost: i64) -> Self {
+        let Node { start, pos, utf16_pos, kind } = node;
+        let (left_id, right_id, weight) = (kind.left_id(), kind.right_id(), kind.weight());
+        let source = kind.source().unwrap();
         let (surface, contents) = match kind {
             NodeKind::BOS | NodeKind::EOS => unreachable!(),
-            NodeKind::Known(morph) => (morph.surface, morph.contents),
+            NodeKind::Known(morph) | NodeKind::User(morph) => (morph.surface, morph.contents),
             NodeKind::Unknown(surface, entry) => (surface, entry.contents),
         };
 
         Token {
-            start: start,
-            surface: surface,
-            contents: contents,
+            start,
+            end: start + surface.len(),
+            pos,
+            char_end: pos + surface.chars().count(),
+            utf16_pos,
+            utf16_end: utf16_pos + surface.encode_utf16().count(),
+            surface,
+            contents,
+            left_id,
+            right_id,
+            weight,
+            source,
+            connection_cost,
+            cost,
         }
     }
 
@@ -39,11 +82,102 @@ impl<'a> Token<'a> {
     }
 
     pub fn end(&self) -> usize {
-        self.start + self.surface().len()
+        self.end
+    }
+
+    /// The start of the token in characters.
+    pub fn char_start(&self) -> usize {
+        self.pos
+    }
+
+    /// The end of the token in characters.
+    pub fn char_end(&self) -> usize {
+        self.char_end
+    }
+
+    /// The start of the token in UTF-16 code units, as used by JavaScript and Java strings.
+    pub fn utf16_start(&self) -> usize {
+        self.utf16_pos
+    }
+
+    /// The end of the token in UTF-16 code units.
+    pub fn utf16_end(&self) -> usize {
+        self.utf16_end
+    }
+
+    pub fn features(&self) -> FeatureIter<'_> {
+        FeatureIter(features::fields(self.contents))
+    }
+
+    /// The features with named fields, e.g. `token.features_as::<Ipadic>()`.
+    pub fn features_as<S: Schema<'a>>(&self) -> S {
+        S::from_fields(features::fields(self.contents))
+    }
+
+    /// The reading in katakana. Readings of unknown words consisting of kana only are estimated
+    /// from their surfaces. See also `Tokenizer::reading`.
+    pub fn reading(&self) -> Option<Cow<'a, str>> {
+        match self.features_as::<Ipadic>().reading {
+            Some(r) => Some(Cow::Borrowed(r)),
+            None if !self.is_known() => reading::estimate(self.surface).map(Cow::Owned),
+            None => None,
+        }
+    }
+
+    /// The pronunciation in katakana, e.g. ワ for the particle は. The estimated reading is used
+    /// for unknown words.
+    pub fn pronunciation(&self) -> Option<Cow<'a, str>> {
+        match self.features_as::<Ipadic>().pronunciation {
+            Some(p) => Some(Cow::Borrowed(p)),
+            None => self.reading(),
+        }
     }
 
-    pub fn features(&self) -> FeatureIter {
-        FeatureIter(self.contents.split(','))
+    /// The Universal POS tag converted from IPADIC parts of speech.
+    pub fn upos(&self) -> Upos {
+        Upos::from_ipadic(&self.features_as())
+    }
+
+    /// IPADIC parts of speech joined with `-`, e.g. `名詞-固有名詞-地域-一般`.
+    pub fn xpos(&self) -> String {
+        ud::xpos(&self.features_as::<Ipadic>())
+    }
+
+    pub fn left_id(&self) -> u16 {
+        self.left_id
+    }
+
+    pub fn right_id(&self) -> u16 {
+        self.right_id
+    }
+
+    /// The word cost of the token.
+    pub fn weight(&self) -> i16 {
+        self.weight
+    }
+
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    /// Whether the token is found in the dictionaries, not estimated as an unknown word.
+    pub fn is_known(&self) -> bool {
+        self.source != Source::Unknown
+    }
+
+    /// The connection cost from the previous token.
+    pub fn connection_cost(&self) -> i64 {
+        self.connection_cost
+    }
+
+    /// The total cost of the path from the beginning up to this token, including its word cost.
+    /// In the search and extended modes, word costs include the penalties for long words.
+    pub fn cost(&self) -> i64 {
+        self.cost
+    }
+
+    pub fn to_buf(&self) -> TokenBuf {
+        TokenBuf::from(self)
     }
 }
 
@@ -53,7 +187,8 @@ impl<'a> fmt::Display for Token<'a> {
     }
 }
 
-pub struct FeatureIter<'a>(Split<'a, char>);
+#[derive(Debug, Clone)]
+pub struct FeatureIter<'a>(Fields<'a>);
 
 impl<'a> Iterator for FeatureIter<'a> {
     type Item = &'a str;
@@ -63,22 +198,660 @@ impl<'a> Iterator for FeatureIter<'a> {
     }
 }
 
-pub struct Tokenizer<'a> {
-    sysdic: SysDic,
-    udic: Option<FstDic<&'a [u8]>>,
+/// One of the results of N-best analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<'a> {
+    tokens: Vec<Token<'a>>,
+    cost: i64,
 }
 
-impl<'a> Tokenizer<'a> {
+impl<'a> Path<'a> {
+    pub fn tokens(&self) -> &[Token<'a>] {
+        &self.tokens
+    }
+
+    pub fn into_tokens(self) -> Vec<Token<'a>> {
+        self.tokens
+    }
+
+    /// The total cost of the path, i.e. the sum of word costs and connection costs.
+    pub fn cost(&self) -> i64 {
+        self.cost
+    }
+}
+
+fn path_tokens<'a>(la: &Lattice<'a>, path: &[NodeId]) -> Vec<Token<'a>> {
+    la.path_nodes(path)
+        .map(|(_, node, connection_cost, cost)| Token::new(node, connection_cost, cost))
+        .collect()
+}
+
+/// Tokenization modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Segments inputs by the minimum cost paths.
+    #[default]
+    Normal,
+    /// Gives penalties to long words so that compound nouns such as 関西国際空港 are split into
+    /// their components. Useful for full-text search.
+    Search,
+    /// In addition to `Search`, splits unknown words into single characters.
+    Extended,
+}
+
+/// Options of tokenization accepted by the methods of `Tokenizer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Options<'a> {
+    mode: Mode,
+    constraints: &'a [Constraint<'a>],
+}
+
+impl<'a> Options<'a> {
+    pub fn new() -> Self {
+        Options::default()
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Considers only the paths that satisfy all the constraints. Offsets of the constraints
+    /// are in the input to be tokenized.
+    pub fn with_constraints(mut self, constraints: &'a [Constraint<'a>]) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn constraints(&self) -> &'a [Constraint<'a>] {
+        self.constraints
+    }
+}
+
+/// A tokenizer owning its dictionaries.
+///
+/// The dictionaries are shared by `Arc`, so cloning a tokenizer is cheap, and it can be stored
+/// anywhere or sent to other threads.
+#[derive(Clone)]
+pub struct Tokenizer {
+    sysdic: Arc<SysDic>,
+    udic: Option<Arc<FstDic<Vec<u8>>>>,
+}
+
+impl Tokenizer {
     pub fn new(sysdic: SysDic) -> Self {
-        Tokenizer { sysdic: sysdic, udic: None }
+        Tokenizer { sysdic: Arc::new(sysdic), udic: None }
+    }
+
+    pub fn sysdic(&self) -> &SysDic {
+        &self.sysdic
+    }
+
+    /// Sets the user dictionary looked up in addition to the system dictionary. Fails with
+    /// `InvalidData` if a morph has context ids out of the matrix of the system dictionary.
+    pub fn with_udic(self, udic: FstDic<Vec<u8>>) -> io::Result<Self> {
+        let matrix = &self.sysdic.matrix;
+        let invalid = udic.morphs()
+            .find(|m| m.left_id >= matrix.height() || m.right_id >= matrix.width());
+        if let Some(m) = invalid {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("context ids of {} are out of the matrix: {}, {}",
+                                              m.surface,
+                                              m.left_id,
+                                              m.right_id)));
+        }
+        Ok(Tokenizer { sysdic: self.sysdic, udic: Some(Arc::new(udic)) })
+    }
+
+    /// Loads the user dictionary from a CSV file. See `FstDic::from_csv` for the format.
+    pub fn with_udic_file<P: AsRef<FilePath>>(self, path: P) -> io::Result<Self> {
+        let udic = FstDic::open(path)?;
+        self.with_udic(udic)
+    }
+
+    pub fn tokenize<'a>(&'a self, input: &'a str) -> Vec<Token<'a>> {
+        let la = Lattice::build(input, &self.sysdic, self.udic.as_deref());
+        path_tokens(&la, &la.best_path())
+    }
+
+    /// Tokenizes the normalized text. Surfaces of the tokens are in the normalized text, while
+    /// their offsets point into the original.
+    pub fn tokenize_normalized<'a>(&'a self, input: &'a Normalized) -> Vec<Token<'a>> {
+        let mut tokens = self.tokenize(input.text());
+        for token in &mut tokens {
+            let start = input.original_start(token.start);
+            let end = input.original_end(token.end);
+            token.start = start.byte;
+            token.end = end.byte;
+            token.pos = start.char;
+            token.char_end = end.char;
+            token.utf16_pos = start.utf16;
+            token.utf16_end = end.utf16;
+        }
+        tokens
+    }
+
+    /// Tokenizes the input with the options. Fails if the constraints of the options are
+    /// invalid for the input.
+    pub fn tokenize_with_options<'a>(&'a self,
+                                     input: &'a str,
+                                     options: &Options<'a>)
+                                     -> Result<Vec<Token<'a>>, ConstraintError> {
+        let la = self.lattice(input, options)?;
+        Ok(path_tokens(&la, &la.best_path()))
     }
 
-    pub fn with_udic<'b>(self, udic: FstDic<&'b [u8]>) -> Tokenizer<'b> {
-        Tokenizer { sysdic: self.sysdic, udic: Some(udic) }
+    /// Same as `tokenize_with_options`, but builds the lattice on the buffers of `workspace`
+    /// and writes the tokens into `out` (cleared first) to save allocations when tokenizing
+    /// many inputs.
+    pub fn tokenize_with<'a>(&'a self,
+                         workspace: &mut Workspace,
+                         input: &'a str,
+                         options: &Options<'a>,
+                         out: &mut Vec<Token<'a>>)
+                         -> Result<(), ConstraintError> {
+        // checked before taking the buffers so that they are kept on errors.
+        check_constraints(input, options.constraints())?;
+        let mut la = Lattice::build_in(::std::mem::take(workspace),
+                                       input,
+                                       &self.sysdic,
+                                       self.udic.as_deref(),
+                                       options)?;
+        out.clear();
+        out.extend(la.best_path_nodes().map(|(_, node, connection_cost, cost)| {
+            Token::new(node, connection_cost, cost)
+        }));
+        *workspace = la.into_workspace();
+        Ok(())
     }
 
-    pub fn tokenize(&'a self, input: &'a str) -> Vec<Token<'a>> {
-        let la = Lattice::build(input, &self.sysdic, self.udic.as_ref());
-        la.into_output().into_iter().map(|node| Token::new(node)).collect()
+    /// Builds the lattice for the input to inspect candidate nodes and their costs.
+    pub fn lattice<'a>(&'a self,
+                       input: &'a str,
+                       options: &Options<'a>)
+                       -> Result<Lattice<'a>, ConstraintError> {
+        Lattice::build_with(input, &self.sysdic, self.udic.as_deref(), options)
+    }
+
+    /// Tokenizes the input and pairs each token with its marginal probability.
+    ///
+    /// See `Lattice::marginals` for the meaning of `temperature`, which must be positive.
+    pub fn tokenize_with_marginals<'a>(&'a self,
+                                   input: &'a str,
+                                   temperature: f64,
+                                   options: &Options<'a>)
+                                   -> Result<Vec<(Token<'a>, f64)>, ConstraintError> {
+        let la = self.lattice(input, options)?;
+        let marginals = la.marginals(temperature);
+        Ok(la.path_nodes(&la.best_path())
+            .map(|(id, node, connection_cost, cost)| {
+                (Token::new(node, connection_cost, cost), marginals[id])
+            })
+            .collect())
+    }
+
+    /// Tokenizes the input and splits it into sentences.
+    pub fn sentences<'a>(&'a self, input: &'a str) -> Vec<Sentence<'a>> {
+        SentenceSplitter::new(&self.sysdic.unknown_dic).split(input, self.tokenize(input))
+    }
+
+    /// Tokenizes the input from `reader` chunk by chunk, and passes the tokens to `f`.
+    /// The memory usage is bounded regardless of the length of lines.
+    ///
+    /// See `TokenStream` for more details. Constraints are not accepted since the input is
+    /// not known in advance.
+    pub fn tokenize_stream<R, F>(&self, reader: R, mode: Mode, mut f: F) -> io::Result<()>
+        where R: BufRead,
+              F: FnMut(Token)
+    {
+        let mut stream = TokenStream::new(self, reader).with_mode(mode);
+        while stream.next_chunk(&mut f)? {}
+        Ok(())
+    }
+
+    /// Tokenizes the inputs on `threads` threads sharing the dictionaries, and returns the
+    /// tokens of each input in the order of `inputs`. If `threads` is 0, the available
+    /// parallelism is used. The options, including the constraints, apply to every input.
+    pub fn tokenize_batch<'a, S>(&'a self,
+                                 inputs: &'a [S],
+                                 threads: usize,
+                                 options: &Options<'a>)
+                                 -> Result<Vec<Vec<Token<'a>>>, ConstraintError>
+        where S: AsRef<str> + Sync
+    {
+        let threads = match threads {
+            0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            n => n,
+        };
+        let threads = ::std::cmp::min(threads, inputs.len());
+        if threads <= 1 {
+            let mut workspace = Workspace::new();
+            return inputs.iter()
+                .map(|input| {
+                    let mut tokens = Vec::new();
+                    self.tokenize_with(&mut workspace, input.as_ref(), options, &mut tokens)?;
+                    Ok(tokens)
+                })
+                .collect();
+        }
+
+        // inputs are taken one by one so that long inputs do not make the other threads idle.
+        let next = AtomicUsize::new(0);
+        let mut results = vec![Vec::new(); inputs.len()];
+        thread::scope(|scope| {
+            let handles = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| -> Result<_, ConstraintError> {
+                        let mut workspace = Workspace::new();
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            if i >= inputs.len() {
+                                return Ok(done);
+                            }
+                            let mut tokens = Vec::new();
+                            self.tokenize_with(&mut workspace,
+                                               inputs[i].as_ref(),
+                                               options,
+                                               &mut tokens)?;
+                            done.push((i, tokens));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                for (i, tokens) in handle.join().unwrap()? {
+                    results[i] = tokens;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(results)
+    }
+
+    /// The reading of the token. In addition to `Token::reading`, readings of unknown words
+    /// containing kanji are estimated by looking up each character in the system dictionary.
+    pub fn reading<'a>(&'a self, token: &Token<'a>) -> Option<Cow<'a, str>> {
+        token.reading().or_else(|| if token.is_known() {
+            None
+        } else {
+            reading::estimate_with_dic(&self.sysdic.dic, token.surface).map(Cow::Owned)
+        })
+    }
+
+    /// Returns at most `n` best paths in ascending order of their costs.
+    pub fn tokenize_nbest<'a>(&'a self,
+                              input: &'a str,
+                              n: usize,
+                              options: &Options<'a>)
+                              -> Result<Vec<Path<'a>>, ConstraintError> {
+        let la = self.lattice(input, options)?;
+        Ok(la.nbest_paths(n)
+            .into_iter()
+            .map(|(path, cost)| {
+                Path {
+                    tokens: path_tokens(&la, &path),
+                    cost,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysdic;
+    use normalize::Normalizer;
+
+    fn surfaces<'a>(tokens: &[Token<'a>]) -> Vec<&'a str> {
+        tokens.iter().map(|t| t.surface).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize("すもももももももものうち");
+        assert_eq!(surfaces(&tokens),
+                   vec!["すもも", "も", "もも", "も", "もも", "の", "うち"]);
+    }
+
+    #[test]
+    fn test_tokenize_with_udic() {
+        fn assert_shareable<T: Send + Sync + Clone + 'static>() {}
+        assert_shareable::<Tokenizer>();
+
+        let path = ::std::env::temp_dir().join(format!("yoin-udic-{}.csv", ::std::process::id()));
+        ::std::fs::write(&path, "ヨイン,1,1,100,名詞,固有名詞,一般,*,*,*,ヨイン,ヨイン,ヨイン\n")
+            .unwrap();
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic()).with_udic_file(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+
+        let tokenizer = tokenizer.clone();
+        let tokens = tokenizer.tokenize("ヨインのすもも");
+        assert_eq!(surfaces(&tokens), vec!["ヨイン", "の", "すもも"]);
+        assert_eq!(tokens[0].features().take(3).collect::<Vec<_>>(),
+                   vec!["名詞", "固有名詞", "一般"]);
+    }
+
+    #[test]
+    fn test_udic_invalid_ids() {
+        let udic = |left_id, right_id| {
+            FstDic::build(&[::dic::Morph {
+                                surface: "ヨイン",
+                                left_id,
+                                right_id,
+                                weight: 100,
+                                contents: "名詞,固有名詞,一般,*,*,*,ヨイン,ヨイン,ヨイン",
+                            }])
+        };
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        assert!(tokenizer.clone().with_udic(udic(3, 3)).is_ok());
+        for &(left_id, right_id) in &[(99, 1), (1, 4)] {
+            let err = tokenizer.clone().with_udic(udic(left_id, right_id)).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        let path = ::std::env::temp_dir().join(format!("yoin-udic-ids-{}.csv",
+                                                       ::std::process::id()));
+        ::std::fs::write(&path, "ヨイン,99,99,100,名詞,固有名詞,一般,*,*,*,ヨイン,ヨイン,ヨイン\n")
+            .unwrap();
+        let result = tokenizer.with_udic_file(&path);
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_tokenize_batch() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let inputs = (0..20)
+            .map(|i| "すもももももももものうち。関西国際空港".repeat(i % 4))
+            .collect::<Vec<_>>();
+        let expected = inputs.iter().map(|s| tokenizer.tokenize(s)).collect::<Vec<_>>();
+        let options = Options::new();
+        for &threads in &[0, 1, 3] {
+            assert_eq!(tokenizer.tokenize_batch(&inputs, threads, &options).unwrap(), expected);
+        }
+        assert!(tokenizer.tokenize_batch::<&str>(&[], 2, &options).unwrap().is_empty());
+
+        let inputs = ["ヨインの関西", "abcのすもも", "も"];
+        let options = Options::new().with_mode(Mode::Extended);
+        let expected = inputs.iter()
+            .map(|s| tokenizer.tokenize_with_options(s, &options).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(surfaces(&expected[0]), vec!["ヨ", "イ", "ン", "の", "関西"]);
+        for &threads in &[1, 2] {
+            assert_eq!(tokenizer.tokenize_batch(&inputs, threads, &options).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_features_as() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize("関西のヨイン");
+        let features = tokens[0].features_as::<Ipadic>();
+        assert_eq!(features.pos(), vec!["名詞", "固有名詞", "地域", "一般"]);
+        assert_eq!(features.reading, Some("カンサイ"));
+        assert_eq!(tokens[2].features_as::<Ipadic>().reading, None);
+        assert_eq!(tokens[1].to_buf().features_as::<Ipadic>().base_form, Some("の"));
+        assert_eq!((tokens[0].upos(), tokens[0].xpos().as_str()),
+                   (Upos::PROPN, "名詞-固有名詞-地域-一般"));
+        assert_eq!((tokens[1].upos(), tokens[1].xpos().as_str()), (Upos::ADP, "助詞-連体化"));
+    }
+
+    #[test]
+    fn test_reading() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize("すもものヨインと鰯桃");
+        let readings = tokens.iter().map(|t| t.reading()).collect::<Vec<_>>();
+        assert_eq!(readings,
+                   vec![Some("スモモ".into()),
+                        Some("ノ".into()),
+                        Some("ヨイン".into()),
+                        Some("ト".into()),
+                        None]);
+        assert_eq!(tokenizer.reading(&tokens[0]), Some("スモモ".into()));
+        // 鰯 is not in the dictionary
+        assert_eq!(tokenizer.reading(&tokens[4]), None);
+        assert_eq!(tokens[2].pronunciation(), Some("ヨイン".into()));
+    }
+
+    #[test]
+    fn test_token_costs() {
+        let udic = FstDic::build(&[::dic::Morph {
+                                       surface: "ヨイン",
+                                       left_id: 1,
+                                       right_id: 1,
+                                       weight: 100,
+                                       contents: "名詞,固有名詞,一般,*,*,*,ヨイン,ヨイン,ヨイン",
+                                   }]);
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic()).with_udic(udic).unwrap();
+        let tokens = tokenizer.tokenize("ヨインのabc");
+        assert_eq!(tokens.iter().map(|t| t.source()).collect::<Vec<_>>(),
+                   vec![Source::UserDic, Source::SystemDic, Source::Unknown]);
+        assert_eq!((tokens[1].left_id(), tokens[1].right_id(), tokens[1].weight()),
+                   (2, 2, 500));
+
+        for path in tokenizer.tokenize_nbest("すもももももももものうち", 3, &Options::new()).unwrap() {
+            let mut cost = 0;
+            for token in path.tokens() {
+                cost += token.connection_cost() + token.weight() as i64;
+                assert_eq!(token.cost(), cost);
+            }
+        }
+        let tokens = tokenizer.tokenize("すもも");
+        // BOS -> NOUN
+        assert_eq!(tokens[0].connection_cost(), -100);
+        assert_eq!(tokens[0].cost(), 900);
+    }
+
+    #[test]
+    fn test_tokenize_with_workspace() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let mut workspace = Workspace::new();
+        let mut tokens = Vec::new();
+        for input in &["すもももももももものうち", "もも", "", "関西国際空港のすもも"] {
+            tokenizer.tokenize_with(&mut workspace, input, &Options::new(), &mut tokens).unwrap();
+            assert_eq!(tokens, tokenizer.tokenize(input));
+        }
+
+        let options = Options::new().with_mode(Mode::Extended);
+        for input in &["ヨインの関西", "すもも", "abc"] {
+            tokenizer.tokenize_with(&mut workspace, input, &options, &mut tokens).unwrap();
+            assert_eq!(tokens, tokenizer.tokenize_with_options(input, &options).unwrap());
+        }
+        assert_eq!(surfaces(&tokens), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_token_offsets() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        // U+20B9F (𠮟) is a surrogate pair in UTF-16.
+        let input = "a\u{20B9F}のすもも";
+        let tokens = tokenizer.tokenize(input);
+        assert_eq!(surfaces(&tokens), vec!["a", "\u{20B9F}", "の", "すもも"]);
+        assert_eq!(tokens.iter().map(|t| (t.char_start(), t.char_end())).collect::<Vec<_>>(),
+                   vec![(0, 1), (1, 2), (2, 3), (3, 6)]);
+        assert_eq!(tokens.iter().map(|t| (t.utf16_start(), t.utf16_end())).collect::<Vec<_>>(),
+                   vec![(0, 1), (1, 3), (3, 4), (4, 7)]);
+    }
+
+    #[test]
+    fn test_tokenize_normalized() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "ｽﾓﾓのＡＢＣ";
+        let normalized = Normalizer::new().with_rule("ｽﾓﾓ", "すもも").normalize(input);
+        let tokens = tokenizer.tokenize_normalized(&normalized);
+        assert_eq!(surfaces(&tokens), vec!["すもも", "の", "ABC"]);
+        assert_eq!(tokens.iter().map(|t| &input[t.start()..t.end()]).collect::<Vec<_>>(),
+                   vec!["ｽﾓﾓ", "の", "ＡＢＣ"]);
+        assert_eq!((tokens[2].char_start(), tokens[2].char_end()), (4, 7));
+        assert_eq!((tokens[2].utf16_start(), tokens[2].utf16_end()), (4, 7));
+    }
+
+    #[test]
+    fn test_tokenize_with_mode() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let normal = Options::new().with_mode(Mode::Normal);
+        let search = Options::new().with_mode(Mode::Search);
+        let extended = Options::new().with_mode(Mode::Extended);
+        let input = "関西国際空港";
+        let tokens = tokenizer.tokenize_with_options(input, &normal).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["関西国際空港"]);
+        let tokens = tokenizer.tokenize_with_options(input, &search).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["関西", "国際", "空港"]);
+        let tokens = tokenizer.tokenize_with_options(input, &extended).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["関西", "国際", "空港"]);
+        let paths = tokenizer.tokenize_nbest(input, 2, &search).unwrap();
+        assert_eq!(surfaces(paths[0].tokens()), vec!["関西", "国際", "空港"]);
+        let mut workspace = Workspace::new();
+        let mut out = Vec::new();
+        tokenizer.tokenize_with(&mut workspace, input, &search, &mut out).unwrap();
+        assert_eq!(surfaces(&out), vec!["関西", "国際", "空港"]);
+
+        let input = "ヨインの関西";
+        let tokens = tokenizer.tokenize_with_options(input, &search).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["ヨイン", "の", "関西"]);
+        let tokens = tokenizer.tokenize_with_options(input, &extended).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["ヨ", "イ", "ン", "の", "関西"]);
+        for token in &tokens {
+            assert_eq!(&input[token.start()..token.end()], token.surface());
+            assert_eq!(token.char_start(), input[..token.start()].chars().count());
+            assert_eq!(token.utf16_start(), input[..token.start()].encode_utf16().count());
+        }
+    }
+
+    #[test]
+    fn test_tokenize_with_marginals() {
+        let input = "すもももももももものうち";
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let tokens = tokenizer.tokenize_with_marginals(input, 1000.0, &Options::new()).unwrap();
+        assert_eq!(tokens.iter().map(|(t, _)| t.clone()).collect::<Vec<_>>(),
+                   tokenizer.tokenize(input));
+        for &(_, p) in &tokens {
+            assert!(0.0 < p && p <= 1.0 + 1e-9);
+        }
+
+        // the characters split from an unknown word share its probability.
+        let options = Options::new().with_mode(Mode::Extended);
+        let tokens = tokenizer.tokenize_with_marginals("ヨインの関西", 1000.0, &options).unwrap();
+        assert_eq!(tokens.iter().map(|(t, _)| t.surface()).collect::<Vec<_>>(),
+                   vec!["ヨ", "イ", "ン", "の", "関西"]);
+        let la = tokenizer.lattice("ヨインの関西", &options).unwrap();
+        let marginals = la.marginals(1000.0);
+        let expected = la.best_path().iter().map(|&id| marginals[id]).collect::<Vec<_>>();
+        assert_eq!(tokens.iter().map(|&(_, p)| p).collect::<Vec<_>>(),
+                   vec![expected[0], expected[0], expected[0], expected[1], expected[2]]);
+    }
+
+    #[test]
+    fn test_tokenize_with_constraints() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let options = |constraints| Options::new().with_constraints(constraints);
+
+        let constraints = [Constraint::Boundary("す".len())];
+        let tokens = tokenizer.tokenize_with_options("すもものうち", &options(&constraints)).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["す", "もも", "の", "うち"]);
+
+        // "すも" is not in the dictionary.
+        let constraints = [Constraint::Token {
+                               start: 0,
+                               end: "すも".len(),
+                               features: None,
+                           }];
+        let tokens = tokenizer.tokenize_with_options("すもものうち", &options(&constraints)).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["すも", "も", "の", "うち"]);
+        assert!(tokens[1].features().next() == Some("助詞"));
+
+        let constraints = [Constraint::Token {
+                               start: "すもも".len(),
+                               end: "すももも".len(),
+                               features: Some("名詞,固有名詞"),
+                           }];
+        let tokens = tokenizer.tokenize_with_options("すもももうち", &options(&constraints)).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["すもも", "も", "うち"]);
+        assert_eq!(tokens[1].features().take(2).collect::<Vec<_>>(),
+                   vec!["名詞", "固有名詞"]);
+
+        // no entry matches, so the pattern itself becomes the features.
+        let constraints = [Constraint::Token {
+                               start: 0,
+                               end: "すもも".len(),
+                               features: Some("名詞,*,人名"),
+                           }];
+        let tokens = tokenizer.tokenize_with_options("すもものうち", &options(&constraints)).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["すもも", "の", "うち"]);
+        assert_eq!(tokens[0].features().collect::<Vec<_>>(), vec!["名詞", "*", "人名"]);
+
+        // a boundary inside a group of unknown characters cuts the group.
+        let constraints = [Constraint::Boundary(2)];
+        let tokens = tokenizer.tokenize_with_options("abcd", &options(&constraints)).unwrap();
+        assert_eq!(surfaces(&tokens), vec!["ab", "cd"]);
+        assert_eq!(tokens[0].features().next(), Some("名詞"));
+    }
+
+    #[test]
+    fn test_invalid_constraints() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let error = |constraints: &[Constraint]| {
+            let options = Options::new().with_constraints(constraints);
+            tokenizer.tokenize_with_options("すもも", &options).err()
+        };
+        let token = |start, end| {
+            Constraint::Token {
+                start,
+                end,
+                features: None,
+            }
+        };
+        assert_eq!(error(&[Constraint::Boundary(1)]), Some(ConstraintError::InvalidOffset(1)));
+        assert_eq!(error(&[Constraint::Boundary(10)]), Some(ConstraintError::InvalidOffset(10)));
+        assert_eq!(error(&[token(3, 3)]),
+                   Some(ConstraintError::EmptyToken { start: 3, end: 3 }));
+        assert_eq!(error(&[token(0, 9), Constraint::Boundary(3)]),
+                   Some(ConstraintError::Conflict(0, 1)));
+        assert_eq!(error(&[token(0, 6), token(3, 9)]), Some(ConstraintError::Conflict(0, 1)));
+        assert_eq!(error(&[token(0, 3), token(0, 3), Constraint::Boundary(3)]), None);
+
+        let typed = |features| {
+            Constraint::Token {
+                start: 0,
+                end: 9,
+                features: Some(features),
+            }
+        };
+        assert_eq!(error(&[typed("名詞"), typed("動詞")]), Some(ConstraintError::Conflict(0, 1)));
+        assert_eq!(error(&[typed("名詞,*,地域"), typed("名詞,一般,*")]), None);
+        assert_eq!(error(&[typed("名詞,*,地域"), token(0, 9), typed("名詞,一般")]), None);
+
+        let inputs = ["すもも", "も"];
+        let constraints = [Constraint::Boundary(6)];
+        let options = Options::new().with_constraints(&constraints);
+        for threads in 1..3 {
+            assert_eq!(tokenizer.tokenize_batch(&inputs, threads, &options).err(),
+                       Some(ConstraintError::InvalidOffset(6)));
+        }
+    }
+
+    #[test]
+    fn test_tokenize_nbest() {
+        let input = "すもももももももものうち";
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let paths = tokenizer.tokenize_nbest(input, 5, &Options::new()).unwrap();
+        assert_eq!(paths.len(), 5);
+        assert_eq!(paths[0].tokens(), &tokenizer.tokenize(input)[..]);
+        for (p, q) in paths.iter().zip(paths.iter().skip(1)) {
+            assert!(p.cost() <= q.cost());
+            assert!(p.tokens() != q.tokens());
+        }
+        for path in &paths {
+            let surface = path.tokens().iter().map(|t| t.surface()).collect::<String>();
+            assert_eq!(surface, input);
+        }
+        assert!(tokenizer.tokenize_nbest(input, 0, &Options::new()).unwrap().is_empty());
     }
 }
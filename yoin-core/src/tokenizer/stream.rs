@@ -0,0 +1,288 @@
+//! Tokenization of inputs read from `io::BufRead` with bounded memory.
+//!
+//! The input is buffered up to a chunk size, and the buffer is cut at safe points: after
+//! sentence terminators, or, if none is found, at a position that every path in the lattice
+//! goes through.
+use std::io::{self, BufRead};
+use std::str;
+
+use sentence;
+use super::{Mode, Options, Token, Tokenizer};
+use super::lattice::Lattice;
+
+/// The default maximum size of the buffer in bytes.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The minimum size of the buffer, which holds any UTF-8 character.
+pub const MIN_CHUNK_SIZE: usize = 4;
+
+fn is_terminator(ch: char) -> bool {
+    sentence::is_terminator(ch) || ch == '\n'
+}
+
+pub struct TokenStream<'t, R> {
+    tokenizer: &'t Tokenizer,
+    reader: R,
+    mode: Mode,
+    chunk_size: usize,
+    buf: String,
+    /// the offset of `buf` in the whole input
+    offset: usize,
+    /// the offset of `buf` in characters
+    char_offset: usize,
+    /// the offset of `buf` in UTF-16 code units
+    utf16_offset: usize,
+    /// bytes of an incomplete character at the end of the last read
+    pending: Vec<u8>,
+    eof: bool,
+}
+
+impl<'t, R: BufRead> TokenStream<'t, R> {
+    pub fn new(tokenizer: &'t Tokenizer, reader: R) -> Self {
+        TokenStream {
+            tokenizer,
+            reader,
+            mode: Mode::Normal,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            buf: String::new(),
+            offset: 0,
+            char_offset: 0,
+            utf16_offset: 0,
+            pending: Vec::new(),
+            eof: false,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the maximum size of the buffer in bytes. Sizes smaller than `MIN_CHUNK_SIZE` are
+    /// rounded up to it.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = ::std::cmp::max(chunk_size, MIN_CHUNK_SIZE);
+        self
+    }
+
+    fn fill_buf(&mut self) -> io::Result<()> {
+        while !self.eof && self.buf.len() < self.chunk_size {
+            let n = {
+                let bytes = self.reader.fill_buf()?;
+                let n = ::std::cmp::min(bytes.len(), self.chunk_size - self.buf.len());
+                self.pending.extend_from_slice(&bytes[..n]);
+                n
+            };
+            self.reader.consume(n);
+            if n == 0 {
+                self.eof = true;
+            }
+            let valid_len = match str::from_utf8(&self.pending) {
+                Ok(_) => self.pending.len(),
+                Err(e) => {
+                    if e.error_len().is_some() || (self.eof && !self.pending.is_empty()) {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "stream did not contain valid UTF-8"));
+                    }
+                    e.valid_up_to()
+                }
+            };
+            self.buf.push_str(unsafe { str::from_utf8_unchecked(&self.pending[..valid_len]) });
+            self.pending.drain(..valid_len);
+            if self.find_sentence_end().is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the end of the last sentence in the buffer.
+    fn find_sentence_end(&self) -> Option<usize> {
+        let mut end = None;
+        let mut chars = self.buf.char_indices().peekable();
+        while let Some((_, ch)) = chars.next() {
+            if !is_terminator(ch) {
+                continue;
+            }
+            while let Some(&(_, c)) = chars.peek() {
+                if !is_terminator(c) && !sentence::is_closing(c) {
+                    break;
+                }
+                chars.next();
+            }
+            match chars.peek() {
+                Some(&(i, _)) => end = Some(i),
+                // a closing bracket may follow in the next read.
+                None if self.eof => end = Some(self.buf.len()),
+                None => (),
+            }
+        }
+        end
+    }
+
+    /// Tokenizes the next chunk of the input and passes its tokens to `f`.
+    /// Returns `Ok(false)` if the input is exhausted.
+    ///
+    /// Offsets of the tokens are in the whole input, while their cumulative costs are from the
+    /// beginning of the chunk.
+    pub fn next_chunk<F: FnMut(Token)>(&mut self, mut f: F) -> io::Result<bool> {
+        self.fill_buf()?;
+        if self.buf.is_empty() {
+            return Ok(false);
+        }
+
+        let end = if self.eof {
+            Some(self.buf.len())
+        } else {
+            self.find_sentence_end()
+        };
+        let cut = {
+            let options = Options::new().with_mode(self.mode);
+            let la = self.tokenizer
+                .lattice(&self.buf[..end.unwrap_or(self.buf.len())], &options)
+                .expect("options without constraints are always valid");
+            let cut = end.unwrap_or_else(|| find_fixed_point(&la));
+            for (_, node, connection_cost, cost) in la.path_nodes(&la.best_path()) {
+                if node.end() > cut {
+                    break;
+                }
+                let mut token = Token::new(node, connection_cost, cost);
+                token.start += self.offset;
+                token.end += self.offset;
+                token.pos += self.char_offset;
+                token.char_end += self.char_offset;
+                token.utf16_pos += self.utf16_offset;
+                token.utf16_end += self.utf16_offset;
+                f(token);
+            }
+            cut
+        };
+        for ch in self.buf[..cut].chars() {
+            self.char_offset += 1;
+            self.utf16_offset += ch.len_utf16();
+        }
+        self.buf.drain(..cut);
+        self.offset += cut;
+        Ok(true)
+    }
+}
+
+/// Finds the last position in the first half of the input that only one node ends at and no
+/// node crosses. The best path up to there does not depend on the rest of the input.
+///
+/// Only the first half is searched since words around the end of the buffer may continue to
+/// the unread input. If no such position is found, this falls back to the boundary of the
+/// best path nearest to the half.
+fn find_fixed_point(la: &Lattice) -> usize {
+    let nodes = la.nodes();
+    let len = nodes[nodes.len() - 1].start;
+    let half = len / 2;
+    let mut ends = vec![0; len + 1];
+    let mut crossings = vec![0i32; len + 1];
+    for node in &nodes[1..nodes.len() - 1] {
+        ends[node.end()] += 1;
+        crossings[node.start + 1] += 1;
+        crossings[node.end()] -= 1;
+    }
+    let mut crossing = 0;
+    let mut cut = None;
+    for p in 1..half + 1 {
+        crossing += crossings[p];
+        if crossing == 0 && ends[p] == 1 {
+            cut = Some(p);
+        }
+    }
+    cut.unwrap_or_else(|| {
+        let path = la.best_path();
+        path.iter()
+            .map(|&id| la.node(id).end())
+            .take_while(|&end| end <= half)
+            .last()
+            .unwrap_or_else(|| la.node(path[0]).end())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::*;
+    use sysdic;
+
+    /// A reader that returns at most `n` bytes at a time.
+    struct Slow<'a>(&'a [u8], usize);
+
+    impl<'a> Read for Slow<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = ::std::cmp::min(self.1, buf.len());
+            (&mut self.0).read(&mut buf[..n])
+        }
+    }
+
+    fn stream_tokens(tokenizer: &Tokenizer,
+                     input: &str,
+                     chunk_size: usize)
+                     -> Vec<(usize, usize, usize, String)> {
+        let reader = io::BufReader::with_capacity(3, Slow(input.as_bytes(), 2));
+        let mut stream = TokenStream::new(tokenizer, reader).with_chunk_size(chunk_size);
+        let mut tokens = Vec::new();
+        while stream.next_chunk(|t| {
+                tokens.push((t.start(), t.char_start(), t.utf16_start(), t.surface().to_string()))
+            })
+            .unwrap() {}
+        tokens
+    }
+
+    #[test]
+    fn test_stream_sentences() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "すもももももももものうち。すもものうち。\nもものうち";
+        let expected = tokenizer.tokenize(input)
+            .into_iter()
+            .map(|t| (t.start(), t.char_start(), t.utf16_start(), t.surface().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(stream_tokens(&tokenizer, input, DEFAULT_CHUNK_SIZE), expected);
+        assert_eq!(stream_tokens(&tokenizer, input, 40), expected);
+    }
+
+    #[test]
+    fn test_stream_long_line() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "すもももももももものうち".repeat(20);
+        let tokens = stream_tokens(&tokenizer, &input, 50);
+        assert_eq!(tokens.iter().map(|t| t.3.as_str()).collect::<String>(), input);
+        for &(start, char_start, utf16_start, ref surface) in &tokens {
+            assert_eq!(&input[start..start + surface.len()], surface);
+            assert_eq!(input[..start].chars().count(), char_start);
+            assert_eq!(input[..start].encode_utf16().count(), utf16_start);
+        }
+    }
+
+    #[test]
+    fn test_find_sentence_end() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let mut stream = TokenStream::new(&tokenizer, io::empty());
+        stream.buf.push_str("もも。》も");
+        assert_eq!(stream.find_sentence_end(), Some("もも。》".len()));
+        stream.buf.truncate("もも。》".len());
+        assert_eq!(stream.find_sentence_end(), None);
+        stream.eof = true;
+        assert_eq!(stream.find_sentence_end(), Some("もも。》".len()));
+    }
+
+    #[test]
+    fn test_stream_tiny_chunk() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input = "すもももももももものうち。";
+        let tokens = stream_tokens(&tokenizer, input, 0);
+        assert_eq!(tokens.iter().map(|t| t.3.as_str()).collect::<String>(), input);
+    }
+
+    #[test]
+    fn test_stream_invalid_utf8() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let input: &[u8] = &[0xE3, 0x81];
+        let mut stream = TokenStream::new(&tokenizer, input);
+        assert_eq!(stream.next_chunk(|_| ()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,166 @@
+//! Universal Dependencies: UPOS tags converted from IPADIC parts of speech, and a writer of the
+//! CoNLL-U format.
+use std::fmt;
+use std::io::{self, Write};
+
+use features::Ipadic;
+use tokenizer::Token;
+
+/// Universal POS tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Upos {
+    ADJ,
+    ADP,
+    ADV,
+    AUX,
+    CCONJ,
+    DET,
+    INTJ,
+    NOUN,
+    NUM,
+    PART,
+    PRON,
+    PROPN,
+    PUNCT,
+    SCONJ,
+    SYM,
+    VERB,
+    X,
+}
+
+impl Upos {
+    /// Converts IPADIC parts of speech to a UPOS tag.
+    pub fn from_ipadic(features: &Ipadic) -> Self {
+        match (features.pos1.unwrap_or(""), features.pos2.unwrap_or("")) {
+            ("名詞", "固有名詞") => Upos::PROPN,
+            ("名詞", "代名詞") => Upos::PRON,
+            ("名詞", "数") => Upos::NUM,
+            ("名詞", "形容動詞語幹") => Upos::ADJ,
+            ("名詞", _) | ("接頭詞", _) => Upos::NOUN,
+            ("動詞", "非自立") => Upos::AUX,
+            ("動詞", _) => Upos::VERB,
+            ("形容詞", _) => Upos::ADJ,
+            ("副詞", _) => Upos::ADV,
+            ("連体詞", _) => Upos::DET,
+            ("接続詞", _) => Upos::CCONJ,
+            ("感動詞", _) | ("フィラー", _) => Upos::INTJ,
+            ("助詞", "接続助詞") => Upos::SCONJ,
+            ("助詞", "並立助詞") => Upos::CCONJ,
+            ("助詞", "終助詞") | ("助詞", "副詞化") => Upos::PART,
+            ("助詞", _) => Upos::ADP,
+            ("助動詞", _) => Upos::AUX,
+            ("記号", "句点") | ("記号", "読点") | ("記号", "括弧開") | ("記号", "括弧閉") => {
+                Upos::PUNCT
+            }
+            ("記号", "空白") => Upos::X,
+            ("記号", _) => Upos::SYM,
+            _ => Upos::X,
+        }
+    }
+}
+
+impl fmt::Display for Upos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Joins IPADIC parts of speech with `-`, e.g. `名詞-固有名詞-地域-一般`.
+pub fn xpos(features: &Ipadic) -> String {
+    features.pos().join("-")
+}
+
+/// Writes analyzed sentences in the CoNLL-U format. Dependencies are not analyzed, so `HEAD`
+/// and `DEPREL` are left empty (`_`), and whitespace tokens are omitted.
+pub struct ConlluWriter<W> {
+    w: W,
+    sent_id: usize,
+}
+
+impl<W: Write> ConlluWriter<W> {
+    pub fn new(w: W) -> Self {
+        ConlluWriter { w, sent_id: 0 }
+    }
+
+    /// Writes a sentence. Sentences without tokens are skipped.
+    pub fn write_sentence(&mut self, text: &str, tokens: &[Token]) -> io::Result<()> {
+        let tokens = tokens.iter()
+            .filter(|t| !t.surface().trim().is_empty())
+            .collect::<Vec<_>>();
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        self.sent_id += 1;
+        writeln!(self.w, "# sent_id = {}", self.sent_id)?;
+        writeln!(self.w, "# text = {}", text)?;
+        for (i, token) in tokens.iter().enumerate() {
+            let features = token.features_as::<Ipadic>();
+            let xpos = xpos(&features);
+            let space_after = match tokens.get(i + 1) {
+                Some(next) if next.start() == token.end() => "SpaceAfter=No",
+                _ => "_",
+            };
+            writeln!(self.w,
+                     "{}\t{}\t{}\t{}\t{}\t_\t_\t_\t_\t{}",
+                     i + 1,
+                     token.surface(),
+                     features.base_form.unwrap_or_else(|| token.surface()),
+                     Upos::from_ipadic(&features),
+                     if xpos.is_empty() { "_" } else { &xpos },
+                     space_after)?;
+        }
+        writeln!(self.w)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use features::{self, Schema};
+    use sysdic;
+    use tokenizer::Tokenizer;
+
+    fn upos(contents: &str) -> Upos {
+        Upos::from_ipadic(&Ipadic::from_fields(features::fields(contents)))
+    }
+
+    #[test]
+    fn test_upos() {
+        assert_eq!(upos("名詞,固有名詞,地域,一般,*,*,関西,カンサイ,カンサイ"), Upos::PROPN);
+        assert_eq!(upos("名詞,一般,*,*,*,*,すもも,スモモ,スモモ"), Upos::NOUN);
+        assert_eq!(upos("動詞,自立,*,*,一段,基本形,食べる,タベル,タベル"), Upos::VERB);
+        assert_eq!(upos("動詞,非自立,*,*,一段,連用形,いる,イ,イ"), Upos::AUX);
+        assert_eq!(upos("助詞,格助詞,一般,*,*,*,が,ガ,ガ"), Upos::ADP);
+        assert_eq!(upos("助詞,接続助詞,*,*,*,*,て,テ,テ"), Upos::SCONJ);
+        assert_eq!(upos("助動詞,*,*,*,特殊・タ,基本形,た,タ,タ"), Upos::AUX);
+        assert_eq!(upos("記号,句点,*,*,*,*,。,。,。"), Upos::PUNCT);
+        assert_eq!(upos(""), Upos::X);
+        assert_eq!(Upos::PROPN.to_string(), "PROPN");
+    }
+
+    #[test]
+    fn test_conllu() {
+        let tokenizer = Tokenizer::new(sysdic::tests::sysdic());
+        let mut w = ConlluWriter::new(Vec::new());
+        for line in &["関西のすもも。", "", "Yoin ヨイン"] {
+            w.write_sentence(line, &tokenizer.tokenize(line)).unwrap();
+        }
+        assert_eq!(String::from_utf8(w.into_inner()).unwrap(),
+                   "# sent_id = 1\n\
+                    # text = 関西のすもも。\n\
+                    1\t関西\t関西\tPROPN\t名詞-固有名詞-地域-一般\t_\t_\t_\t_\tSpaceAfter=No\n\
+                    2\tの\tの\tADP\t助詞-連体化\t_\t_\t_\t_\tSpaceAfter=No\n\
+                    3\tすもも\tすもも\tNOUN\t名詞-一般\t_\t_\t_\t_\tSpaceAfter=No\n\
+                    4\t。\t。\tPUNCT\t記号-句点\t_\t_\t_\t_\t_\n\
+                    \n\
+                    # sent_id = 2\n\
+                    # text = Yoin ヨイン\n\
+                    1\tYoin\tYoin\tPROPN\t名詞-固有名詞-組織\t_\t_\t_\t_\t_\n\
+                    2\tヨイン\tヨイン\tNOUN\t名詞-一般\t_\t_\t_\t_\t_\n\
+                    \n");
+    }
+}